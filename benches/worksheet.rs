@@ -0,0 +1,69 @@
+use calcagebra_ls::document::Document;
+use criterion::{Criterion, criterion_group, criterion_main};
+use tower_lsp::lsp_types::{Position, Range, TextDocumentContentChangeEvent};
+
+const LINES: usize = 10_000;
+
+/// A worksheet of `LINES` lines mixing declarations, doc comments and multiline
+/// statements
+fn worksheet() -> String {
+    let mut text = String::new();
+
+    for i in 0..LINES / 10 {
+        text += &format!(
+            "/// Scales `x` by {i}\n\
+             fn f{i}(x: R): R = {i}x + 1\n\
+             let a{i} = f{i}(2) ^ 2\n\
+             let m{i} = [1, 2;\n\
+             \x20   3, {i}]\n\
+             let b{i} = if a{i} > 10 then\n\
+             \x20   a{i} / 2\n\
+             else a{i} end\n\
+             // {i} done\n\
+             a{i} + b{i}\n"
+        );
+    }
+
+    text
+}
+
+fn change(line: u32, character: u32, len: u32, text: &str) -> TextDocumentContentChangeEvent {
+    TextDocumentContentChangeEvent {
+        range: Some(Range::new(
+            Position::new(line, character),
+            Position::new(line, character + len),
+        )),
+        range_length: None,
+        text: text.to_string(),
+    }
+}
+
+fn bench(c: &mut Criterion) {
+    let text = worksheet();
+
+    c.bench_function("open 10k lines", |b| {
+        b.iter(|| Document::new(text.clone(), 0))
+    });
+
+    let mut document = Document::new(text, 0);
+    let middle = LINES as u32 / 2 + 2;
+
+    // Typing a digit into a statement in the middle of the worksheet and deleting it
+    c.bench_function("edit a line of 10k", |b| {
+        b.iter(|| {
+            document.edit(&change(middle, 11, 0, "1"), 1);
+            document.edit(&change(middle, 11, 1, ""), 2);
+        })
+    });
+
+    // Opening a bracket reparses everything up to the next declaration
+    c.bench_function("unbalance a line of 10k", |b| {
+        b.iter(|| {
+            document.edit(&change(middle, 11, 0, "("), 1);
+            document.edit(&change(middle, 11, 1, ""), 2);
+        })
+    });
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -1,34 +1,230 @@
 use std::ops::RangeInclusive;
-use crate::{standardlibrary::{self, STD}, token::Token, types::NumberType};
+use crate::{number::Literal, standardlibrary::{self, STD}, token::Token, types::NumberType};
+
+/// Lexer offsets of a piece of source, ending one past its last char like token ranges
+pub type Span = RangeInclusive<usize>;
 
 #[derive(Debug, Clone, PartialEq)]
 
 pub enum AstNode {
-	Assignment((String, Option<NumberType>), Expression),
-	FunctionCall(String, Vec<Expression>),
-	FunctionDeclaration(String, Vec<(String, NumberType)>, NumberType, Expression),
-	Error(String, RangeInclusive<usize>),
+	Assignment((String, Option<NumberType>), Expression, Option<String>, Spans),
+	FunctionCall(String, Vec<Expression>, Spans),
+	FunctionDeclaration(String, Vec<(String, NumberType)>, NumberType, Expression, Option<String>, Spans),
+	Use(String, Spans),
+	Error(String, Span),
+	Warning(Lint, String, Span),
+}
+
+/// The kinds of warnings, each can be allowed or turned into an error by the workspace
+/// configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+	DivisionByZero,
+	Domain,
+	Overflow,
+	/// Recursion that never ends
+	Recursion,
+}
+
+impl Lint {
+	pub fn parse(name: &str) -> Option<Self> {
+		match name {
+			"division_by_zero" => Some(Self::DivisionByZero),
+			"domain" => Some(Self::Domain),
+			"overflow" => Some(Self::Overflow),
+			"recursion" => Some(Self::Recursion),
+			_ => None,
+		}
+	}
+}
+
+/// Where a statement and its parts are in the source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spans {
+	/// The declared or called name, the path of a `use`
+	pub name: Span,
+	/// The type written after `:`, the return type of a function
+	pub annotation: Option<Span>,
+	/// The name and annotation of each parameter of a function declaration
+	pub params: Vec<(Span, Option<Span>)>,
+	/// The whole statement
+	pub node: Span,
+}
+
+impl AstNode {
+	pub fn span(&self) -> &Span {
+		match self {
+			AstNode::Assignment(.., spans)
+			| AstNode::FunctionCall(.., spans)
+			| AstNode::FunctionDeclaration(.., spans)
+			| AstNode::Use(_, spans) => &spans.node,
+			AstNode::Error(_, span) | AstNode::Warning(_, _, span) => span,
+		}
+	}
+
+	/// The calls the statement makes, with their arguments and the span of each called
+	/// name, in source order. For a function declaration these are the calls in its body
+	pub fn calls(&self) -> Vec<(&str, &[Expression], Span)> {
+		match self {
+			AstNode::Assignment(_, expr, ..) | AstNode::FunctionDeclaration(.., expr, _, _) => expr.calls(),
+			AstNode::FunctionCall(name, args, spans) => {
+				let call = (name.as_str(), args.as_slice(), spans.name.clone());
+
+				[call].into_iter().chain(args.iter().flat_map(Expression::calls)).collect()
+			}
+			AstNode::Use(..) | AstNode::Error(..) | AstNode::Warning(..) => vec![],
+		}
+	}
+
+	/// Moves every span of the node by `by` offsets, for when the text before it changes
+	/// length
+	pub fn shift(&mut self, by: isize) {
+		match self {
+			AstNode::Assignment(_, expr, _, spans) | AstNode::FunctionDeclaration(.., expr, _, spans) => {
+				expr.shift(by);
+				spans.shift(by);
+			}
+			AstNode::FunctionCall(_, args, spans) => {
+				args.iter_mut().for_each(|arg| arg.shift(by));
+				spans.shift(by);
+			}
+			AstNode::Use(_, spans) => spans.shift(by),
+			AstNode::Error(_, span) | AstNode::Warning(_, _, span) => shift(span, by),
+		}
+	}
+}
+
+impl Spans {
+	fn shift(&mut self, by: isize) {
+		shift(&mut self.name, by);
+		shift(&mut self.node, by);
+
+		for span in self.params.iter_mut().flat_map(|(name, annotation)| [Some(name), annotation.as_mut()]).chain([self.annotation.as_mut()]).flatten() {
+			shift(span, by);
+		}
+	}
+}
+
+fn shift(span: &mut Span, by: isize) {
+	*span = span.start().saturating_add_signed(by)..=span.end().saturating_add_signed(by);
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
-	Abs(Box<Expression>),
-	Binary(Box<Expression>, Token, Box<Expression>),
-	Branched(Box<Expression>, Box<Expression>, Box<Expression>),
-	Identifier(String),
-	Integer(i32),
-	Real(f32),
-	Matrix(Vec<Vec<Expression>>),
-	FunctionCall(String, Vec<Expression>),
-	Error
+	Abs(Box<Expression>, Span),
+	Binary(Box<Expression>, Token, Box<Expression>, Span),
+	Branched(Box<Expression>, Box<Expression>, Box<Expression>, Span),
+	Identifier(String, Span),
+	Integer(Literal, Span),
+	Real(Literal, Span),
+	Imaginary(Literal, Span),
+	Matrix(Vec<Vec<Expression>>, Span),
+	FunctionCall(String, Vec<Expression>, Span),
+	Error(Span)
 }
 
 impl Expression {
+	pub fn span(&self) -> &Span {
+		match self {
+			Expression::Abs(.., span)
+			| Expression::Binary(.., span)
+			| Expression::Branched(.., span)
+			| Expression::Identifier(.., span)
+			| Expression::Integer(.., span)
+			| Expression::Real(.., span)
+			| Expression::Imaginary(.., span)
+			| Expression::Matrix(.., span)
+			| Expression::FunctionCall(.., span)
+			| Expression::Error(span) => span,
+		}
+	}
+
+	/// The expressions directly inside this one, in source order
+	pub fn children(&self) -> Vec<&Expression> {
+		match self {
+			Expression::Abs(expr, _) => vec![expr],
+			Expression::Binary(lhs, _, rhs, _) => vec![lhs, rhs],
+			Expression::Branched(condition, then, otherwise, _) => vec![condition, then, otherwise],
+			Expression::Matrix(rows, _) => rows.iter().flatten().collect(),
+			Expression::FunctionCall(_, args, _) => args.iter().collect(),
+			Expression::Identifier(..)
+			| Expression::Integer(..)
+			| Expression::Real(..)
+			| Expression::Imaginary(..)
+			| Expression::Error(_) => vec![],
+		}
+	}
+
+	/// The calls made in the expression, with their arguments and the span of each called
+	/// name, in source order
+	pub fn calls(&self) -> Vec<(&str, &[Expression], Span)> {
+		match self {
+			Expression::FunctionCall(name, args, span) => {
+				let call = (name.as_str(), args.as_slice(), *span.start()..=span.start() + name.chars().count());
+
+				[call].into_iter().chain(args.iter().flat_map(Expression::calls)).collect()
+			}
+			Expression::Abs(expr, _) => expr.calls(),
+			Expression::Binary(lhs, _, rhs, _) => [lhs, rhs].into_iter().flat_map(|expr| expr.calls()).collect(),
+			Expression::Branched(condition, then, otherwise, _) => {
+				[condition, then, otherwise].into_iter().flat_map(|expr| expr.calls()).collect()
+			}
+			Expression::Matrix(rows, _) => rows.iter().flatten().flat_map(Expression::calls).collect(),
+			Expression::Identifier(..)
+			| Expression::Integer(..)
+			| Expression::Real(..)
+			| Expression::Imaginary(..)
+			| Expression::Error(_) => vec![],
+		}
+	}
+
+	pub fn shift(&mut self, by: isize) {
+		match self {
+			Expression::Abs(expr, span) => {
+				expr.shift(by);
+				shift(span, by);
+			}
+			Expression::Binary(lhs, _, rhs, span) => {
+				lhs.shift(by);
+				rhs.shift(by);
+				shift(span, by);
+			}
+			Expression::Branched(condition, then, otherwise, span) => {
+				condition.shift(by);
+				then.shift(by);
+				otherwise.shift(by);
+				shift(span, by);
+			}
+			Expression::Matrix(rows, span) => {
+				rows.iter_mut().flatten().for_each(|expr| expr.shift(by));
+				shift(span, by);
+			}
+			Expression::FunctionCall(_, args, span) => {
+				args.iter_mut().for_each(|arg| arg.shift(by));
+				shift(span, by);
+			}
+			Expression::Identifier(_, span)
+			| Expression::Integer(_, span)
+			| Expression::Real(_, span)
+			| Expression::Imaginary(_, span)
+			| Expression::Error(span) => shift(span, by),
+		}
+	}
+
 	pub fn infer_datatype(&self) -> Option<NumberType> {
 		match self {
-			Expression::Abs(expression) => expression.infer_datatype(),
-			Expression::Branched(..) => None,
-			Expression::Binary(lhs, _, rhs) => {
+			Expression::Abs(expression, _) => match expression.infer_datatype()? {
+				NumberType::Complex => Some(NumberType::Real),
+				NumberType::Matrix | NumberType::Bool => None,
+				r#type => Some(r#type),
+			},
+			Expression::Branched(_, then, otherwise, _) => {
+				let then = then.infer_datatype()?;
+
+				(otherwise.infer_datatype()? == then).then_some(then)
+			}
+			Expression::Binary(_, op, ..) if op.is_comparison() => Some(NumberType::Bool),
+			Expression::Binary(lhs, _, rhs, _) => {
 				let lhs = Self::infer_datatype(lhs);
 				let rhs = Self::infer_datatype(rhs);
 
@@ -36,31 +232,35 @@ impl Expression {
 					return None;
 				};
 
-				let lhs = lhs.unwrap();
-				let rhs = rhs.unwrap();
+				let lhs = lhs.unwrap().base();
+				let rhs = rhs.unwrap().base();
+
+				// Arithmetic isn't defined on truth values, the parser reports it
+				if lhs == NumberType::Bool || rhs == NumberType::Bool {
+					return None;
+				}
 
 				Some(match (lhs, rhs) {
-					(NumberType::Int, NumberType::Int) => NumberType::Int,
-					(NumberType::Int, NumberType::Real)
-					| (NumberType::Real, NumberType::Int)
-					| (NumberType::Real, NumberType::Real) => NumberType::Real,
-					(NumberType::Complex, _) | (_, NumberType::Complex) => NumberType::Complex,
+					(NumberType::Unknown, _) | (_, NumberType::Unknown) => NumberType::Unknown,
 					(NumberType::Matrix, _) | (_, NumberType::Matrix) => NumberType::Matrix,
-					(NumberType::Unknown, _) | (_, NumberType::Unknown) => NumberType::Unknown
+					(NumberType::Complex, _) | (_, NumberType::Complex) => NumberType::Complex,
+					(NumberType::Int, NumberType::Int) => NumberType::Int,
+					_ => NumberType::Real,
 				})
 			}
-			Expression::Identifier(_) => None,
+			Expression::Identifier(..) => None,
 			Expression::Real(..) => Some(NumberType::Real),
 			Expression::Integer(..) => Some(NumberType::Int),
+			Expression::Imaginary(..) => Some(NumberType::Complex),
 			Expression::Matrix(..) => Some(NumberType::Matrix),
-			Expression::FunctionCall(ident, _) => {
+			Expression::FunctionCall(ident, ..) => {
 				if STD.contains(&ident.as_str()) {
 					Some(standardlibrary::internal_type_map(ident).1)
 				} else {
 					None
 				}
 			}
-			Expression::Error => None
+			Expression::Error(_) => None
 		}
 	}
 }
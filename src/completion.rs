@@ -0,0 +1,163 @@
+use crate::{
+    parser::Parser,
+    syntax::SyntaxNode,
+    token::{Token, TokenInfo},
+    types::NumberType,
+};
+
+/// Matrix literals offered as snippets
+pub const TEMPLATES: [(&str, &str); 2] = [
+    ("matrix2x2", "[${1:a}, ${2:b}; ${3:c}, ${4:d}]$0"),
+    (
+        "matrix3x3",
+        "[${1:a}, ${2:b}, ${3:c}; ${4:d}, ${5:e}, ${6:f}; ${7:g}, ${8:h}, ${9:i}]$0",
+    ),
+];
+
+/// Where in a statement the cursor is, decides which completions make sense
+#[derive(Debug, Clone, PartialEq)]
+pub enum Context {
+    /// Anywhere an expression or a new statement can start
+    Expression,
+    /// After the `:` of a `let` or `fn` header
+    TypeAnnotation,
+    /// The name being declared by `let` or `fn`, one of the parameter names or the path
+    /// of a `use`
+    Declaration,
+    /// Inside the call to a function, at the given argument
+    Argument(String, usize),
+    /// Inside the condition of an `if` block
+    Condition,
+}
+
+impl Context {
+    /// Classifies the text before the cursor, without the word being typed
+    pub fn new(before: &str) -> Self {
+        let parser = Parser::new(&SyntaxNode::parse(before));
+
+        let Some(statement) = parser.statements().last() else {
+            return Self::Expression;
+        };
+
+        // On a blank line the last statement has ended unless something is still open
+        let blank = before
+            .rsplit('\n')
+            .next()
+            .is_none_or(|f| f.trim().is_empty());
+
+        if blank && depth(statement) == 0 {
+            return Self::Expression;
+        }
+
+        let header = matches!(statement[0].token, Token::Let | Token::Fn)
+            && !statement.iter().any(|t| t.token == Token::Eq);
+
+        match statement.last().map(|t| &t.token) {
+            Some(Token::Colon) if header => return Self::TypeAnnotation,
+            Some(Token::Let | Token::Fn | Token::Use) if statement.len() == 1 => {
+                return Self::Declaration;
+            }
+            Some(Token::LParen | Token::Comma) if header && statement[0].token == Token::Fn => {
+                return Self::Declaration;
+            }
+            _ => {}
+        }
+
+        if let Some((name, argument)) = enclosing_call(statement) {
+            return Self::Argument(name, argument);
+        }
+
+        let block = statement
+            .iter()
+            .rev()
+            .find(|t| matches!(t.token, Token::If | Token::Then | Token::Else));
+
+        if block.is_some_and(|t| t.token == Token::If) {
+            return Self::Condition;
+        }
+
+        Self::Expression
+    }
+}
+
+/// Snippet inserted for a keyword that starts a declaration or a block
+pub fn keyword_snippet(keyword: &str) -> Option<&'static str> {
+    match keyword {
+        "let" => Some("let ${1:name} = $0"),
+        "fn" => Some("fn ${1:name}(${2:x}: ${3:R}): ${4:R} = $0"),
+        "use" => Some("use \"${1:path}.cal\"$0"),
+        "if" => Some("if $1 then $2 else $3 end"),
+        _ => None,
+    }
+}
+
+/// Snippet for a call to `name` with a placeholder for every parameter
+pub fn call_snippet(name: &str, params: &[String]) -> String {
+    format!(
+        "{name}({})$0",
+        params
+            .iter()
+            .enumerate()
+            .map(|(i, param)| format!("${{{}:{param}}}", i + 1))
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
+
+/// Finds the call whose parentheses are still open at the end of `statement`, with the
+/// index of the argument being written
+pub fn enclosing_call(statement: &[TokenInfo]) -> Option<(String, usize)> {
+    let mut depth = 0;
+    let mut argument = 0;
+
+    for (i, tokeninfo) in statement.iter().enumerate().rev() {
+        match tokeninfo.token {
+            Token::RParen | Token::RSquare => depth += 1,
+            Token::LSquare if depth == 0 => argument = 0,
+            Token::LParen if depth == 0 => {
+                return match &statement.get(i.checked_sub(1)?)?.token {
+                    Token::Identifier(name) => Some((name.to_string(), argument)),
+                    _ => None,
+                };
+            }
+            Token::LParen | Token::LSquare => depth -= 1,
+            Token::Comma if depth == 0 => argument += 1,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// How well a value of type `candidate` fits a parameter accepting `expected`: 0 for an
+/// exact match, 1 when it is promoted to the expected type and 2 otherwise
+pub fn rank(candidate: Option<NumberType>, expected: &[NumberType]) -> usize {
+    let Some(candidate) = candidate.map(NumberType::base) else {
+        return 2;
+    };
+
+    if expected.contains(&candidate) {
+        return 0;
+    }
+
+    let promoted = match candidate {
+        NumberType::Int => [NumberType::Real, NumberType::Complex].as_slice(),
+        NumberType::Real => [NumberType::Complex].as_slice(),
+        _ => [].as_slice(),
+    };
+
+    if expected.iter().any(|r#type| promoted.contains(r#type)) {
+        1
+    } else {
+        2
+    }
+}
+
+/// Nesting of brackets and `if` blocks still open at the end of `statement`
+fn depth(statement: &[TokenInfo]) -> usize {
+    statement.iter().fold(0usize, |depth, t| match t.token {
+        Token::LParen | Token::LSquare | Token::LCurly | Token::If => depth + 1,
+        Token::RParen | Token::RSquare | Token::RCurly | Token::End => depth.saturating_sub(1),
+        _ => depth,
+    })
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::{ast::Lint, evaluator::Value, token::Token, types::NumberType};
+
+/// Name of the configuration file at the root of a workspace folder
+pub const CONFIG_FILE: &str = "calcagebra.toml";
+
+/// Settings of a workspace folder, read from its `calcagebra.toml`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Level of each lint, the ones missing are warnings
+    pub lints: HashMap<Lint, Level>,
+    /// Type of the function parameters and return types written without one that
+    /// inference can't decide
+    pub default_type: NumberType,
+    pub format: Format,
+    /// Folders `use` paths are also looked up in, relative to the workspace folder
+    pub include: Vec<PathBuf>,
+    /// Constants defined for every file besides `pi`, `π` and `e`
+    pub constants: HashMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    /// Not reported
+    Allow,
+    Warn,
+    /// Reported as an error
+    Deny,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Format {
+    /// Spaces per level of nesting, the editor's tab size when unset
+    pub indent: Option<usize>,
+    /// Whether binary operators are surrounded by spaces, `1 + 2` rather than `1+2`
+    pub spaced_operators: bool,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self {
+            indent: None,
+            spaced_operators: true,
+        }
+    }
+}
+
+/// The file as written, names are checked once it is read
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct File {
+    lints: HashMap<String, Level>,
+    default_type: Option<String>,
+    format: Format,
+    include: Vec<PathBuf>,
+    constants: HashMap<String, Constant>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged, expecting = "expected a number")]
+enum Constant {
+    Int(i64),
+    Real(f64),
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            lints: HashMap::new(),
+            default_type: NumberType::Real,
+            format: Format::default(),
+            include: vec![],
+            constants: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads the configuration of the workspace folder `root`, the default one when it
+    /// has none
+    pub fn read(root: &Path) -> Result<Self, String> {
+        let path = root.join(CONFIG_FILE);
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(format!("{}: {err}", path.display())),
+        };
+
+        Self::parse(&text).map_err(|err| format!("{}: {err}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let file = toml::from_str::<File>(text).map_err(|err| err.to_string())?;
+
+        let mut config = Self {
+            format: file.format,
+            include: file.include,
+            ..Self::default()
+        };
+
+        for (name, level) in file.lints {
+            let lint = Lint::parse(&name).ok_or_else(|| format!("unknown lint `{name}`"))?;
+
+            config.lints.insert(lint, level);
+        }
+
+        if let Some(name) = file.default_type {
+            config.default_type = NumberType::parse(&name)
+                .filter(|r#type| r#type.is_number_set())
+                .ok_or_else(|| format!("`{name}` is not a number type"))?;
+        }
+
+        for (name, constant) in file.constants {
+            if !matches!(Token::new(name.clone()), Token::Identifier(_)) {
+                return Err(format!("`{name}` is not a valid constant name"));
+            }
+
+            let value = match constant {
+                Constant::Int(n) => i32::try_from(n)
+                    .map(Value::Int)
+                    .map_err(|_| format!("`{name}` does not fit in an integer"))?,
+                Constant::Real(n) => Value::Real(n as f32),
+            };
+
+            config.constants.insert(name, value);
+        }
+
+        Ok(config)
+    }
+
+    pub fn level(&self, lint: Lint) -> Level {
+        self.lints.get(&lint).copied().unwrap_or(Level::Warn)
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{
+    ast::Expression,
+    evaluator::{self, EvalError, MAX_STEPS, Value},
+    standardlibrary::STD,
+    token::Token,
+};
+
+/// Folds the parts of `expr` built only from literals and builtin constants,
+/// collecting every error that is certain to happen at runtime. Folding stops where it
+/// would take more steps than an evaluation may
+pub fn fold(expr: &Expression, errors: &mut Vec<EvalError>) -> Option<Value> {
+    Folder { errors, steps: 0 }.fold(expr)
+}
+
+struct Folder<'a> {
+    errors: &'a mut Vec<EvalError>,
+    steps: usize,
+}
+
+impl Folder<'_> {
+    fn fold(&mut self, expr: &Expression) -> Option<Value> {
+        self.charge(1)?;
+
+        match expr {
+            Expression::Abs(expr, _) => {
+                let value = self.fold(expr)?;
+                self.charge(evaluator::cost("abs", std::slice::from_ref(&value)))?;
+                self.report(evaluator::abs(value))
+            }
+            Expression::Binary(lhs, Token::Belongs, rhs, _) => match &**rhs {
+                // Misused sets are reported by the parser
+                Expression::Identifier(set, _) => evaluator::belongs(self.fold(lhs)?, set).ok(),
+                _ => None,
+            },
+            Expression::Binary(lhs, op, rhs, _) => {
+                let lhs = self.fold(lhs);
+                let rhs = self.fold(rhs);
+
+                if matches!(op, Token::Div | Token::Rem) && rhs.as_ref().is_some_and(is_zero) {
+                    self.errors.push(EvalError::DivisionByZero);
+                    return None;
+                }
+
+                let (lhs, rhs) = (lhs?, rhs?);
+                self.charge(evaluator::cost(
+                    evaluator::operation(op),
+                    &[lhs.clone(), rhs.clone()],
+                ))?;
+
+                self.report(evaluator::apply(lhs, op, rhs))
+            }
+            Expression::Branched(condition, then, otherwise, _) => {
+                // A branch a constant condition never takes can't fail
+                match self.fold(condition) {
+                    Some(Value::Bool(true)) => self.fold(then),
+                    Some(Value::Bool(false)) => self.fold(otherwise),
+                    _ => {
+                        self.fold(then);
+                        self.fold(otherwise);
+
+                        None
+                    }
+                }
+            }
+            Expression::Identifier(ident, _) => evaluator::constant(ident),
+            // Literals outside of Z are reported by the parser
+            Expression::Integer(n, _) => n.to_i32().map(Value::Int),
+            Expression::Real(n, _) => self.report(evaluator::finite(n.to_f32())),
+            Expression::Imaginary(n, _) => self.report(evaluator::imaginary_literal(n.to_f32())),
+            Expression::Matrix(rows, _) => {
+                let rows = rows
+                    .iter()
+                    .map(|row| {
+                        row.iter()
+                            .map(|expr| self.fold(expr))
+                            .collect::<Vec<Option<Value>>>()
+                    })
+                    .collect::<Vec<Vec<Option<Value>>>>();
+
+                rows.into_iter()
+                    .map(|row| row.into_iter().collect::<Option<Vec<Value>>>())
+                    .collect::<Option<Vec<Vec<Value>>>>()
+                    .map(Value::Matrix)
+            }
+            Expression::FunctionCall(name, args, _) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.fold(arg))
+                    .collect::<Vec<Option<Value>>>();
+
+                if !STD.contains(&name.as_str()) {
+                    return None;
+                }
+
+                if matches!(name.as_str(), "div" | "rem")
+                    && args
+                        .get(1)
+                        .is_some_and(|arg| arg.as_ref().is_some_and(is_zero))
+                {
+                    self.errors.push(EvalError::DivisionByZero);
+                    return None;
+                }
+
+                let args = args.into_iter().collect::<Option<Vec<Value>>>()?;
+                self.charge(evaluator::cost(name, &args))?;
+
+                self.report(evaluator::call_std(name, args))
+            }
+            Expression::Error(_) => None,
+        }
+    }
+
+    /// Keeps the errors that can be decided statically, unresolved names and type
+    /// mismatches are left to the other passes
+    fn report(&mut self, value: Result<Value, EvalError>) -> Option<Value> {
+        match value {
+            Ok(value) => Some(value),
+            Err(err @ (EvalError::DivisionByZero | EvalError::Domain(_) | EvalError::Overflow)) => {
+                self.errors.push(err);
+                None
+            }
+            Err(_) => None,
+        }
+    }
+
+    /// Counts `steps` more, none once the budget is spent
+    fn charge(&mut self, steps: usize) -> Option<()> {
+        self.steps = self.steps.saturating_add(steps);
+
+        (self.steps <= MAX_STEPS).then_some(())
+    }
+}
+
+fn is_zero(value: &Value) -> bool {
+    match value {
+        Value::Int(n) => *n == 0,
+        Value::Real(n) => *n == 0.0,
+        Value::Complex(re, im) => *re == 0.0 && *im == 0.0,
+        Value::Matrix(_) | Value::Bool(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::AstNode, parser::Parser, syntax::SyntaxNode};
+
+    /// Folds the value of `let x = ...`
+    fn fold_value(source: &str) -> (Option<Value>, Vec<EvalError>) {
+        let ast = Parser::new(&SyntaxNode::parse(&format!("let x = {source}")))
+            .ast()
+            .unwrap();
+
+        let Some(AstNode::Assignment(_, expr, ..)) = ast.last() else {
+            panic!("expected an assignment, found {ast:?}");
+        };
+
+        let mut errors = vec![];
+
+        (fold(expr, &mut errors), errors)
+    }
+
+    #[test]
+    fn folds_matrix_builtins() {
+        assert_eq!(
+            fold_value("determinant([1, 2; 3, 4])"),
+            (Some(Value::Real(-2.0)), vec![])
+        );
+        assert_eq!(
+            fold_value("inverse([1, 2; 2, 4])"),
+            (
+                None,
+                vec![EvalError::Domain(
+                    "inverse of a singular matrix".to_string()
+                )]
+            )
+        );
+    }
+
+    #[test]
+    fn stops_at_step_budget() {
+        let rows = vec![vec!["1"; 50].join(", "); 50].join("; ");
+
+        assert_eq!(fold_value(&format!("adj([{rows}])")), (None, vec![]));
+    }
+}
@@ -0,0 +1,925 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use tower_lsp::jsonrpc;
+use tower_lsp::lsp_types::{
+    Diagnostic, DiagnosticSeverity, Range, TextDocumentContentChangeEvent, Url,
+};
+
+use crate::{
+    ast::{AstNode, Expression, Span},
+    config::{Config, Level},
+    document::Document,
+    evaluator::{EvalError, Evaluator, Value, constant},
+    inference,
+    position::LineIndex,
+    recursion::Recursion,
+    token::TokenInfo,
+    types::NumberType,
+};
+
+/// A query gave up because the documents changed since the snapshot it runs on was taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+pub type Cancellable<T> = Result<T, Cancelled>;
+
+/// The client retries requests answered with `ContentModified` once it has sent its edits
+impl From<Cancelled> for jsonrpc::Error {
+    fn from(_: Cancelled) -> Self {
+        jsonrpc::Error::content_modified()
+    }
+}
+
+/// Value of each node of the AST, in order, `None` for the nodes producing none
+pub type Evaluation = Vec<Option<Result<Value, EvalError>>>;
+
+/// The declarations in scope in a file, a name may be declared more than once
+#[derive(Debug, Default, PartialEq)]
+pub struct Symbols {
+    /// Declarations of the file itself, in source order
+    pub declarations: Vec<AstNode>,
+    /// Declarations brought in by `use`, with the file they are in, in the order the
+    /// files run
+    pub imported: Vec<(Url, AstNode)>,
+}
+
+impl Symbols {
+    /// Every declaration in the order they run, the imported ones first
+    pub fn iter(&self) -> impl Iterator<Item = &AstNode> {
+        self.imported
+            .iter()
+            .map(|(_, node)| node)
+            .chain(&self.declarations)
+    }
+
+    /// The first declaration of the function `name`, the file's own before the imported
+    /// ones
+    pub fn function(&self, name: &str) -> Option<&AstNode> {
+        self.find_function(name).map(|(_, node)| node)
+    }
+
+    /// The last assignment to `name`, the one in effect once the file has run
+    pub fn variable(&self, name: &str) -> Option<&AstNode> {
+        self.find_variable(name).map(|(_, node)| node)
+    }
+
+    /// The declaration `name` refers to, a function before a variable, with the file it
+    /// is in or `None` for the file itself
+    pub fn lookup(&self, name: &str) -> Option<(Option<&Url>, &AstNode)> {
+        self.find_function(name)
+            .or_else(|| self.find_variable(name))
+    }
+
+    fn find_function(&self, name: &str) -> Option<(Option<&Url>, &AstNode)> {
+        let function = |node: &AstNode| matches!(node, AstNode::FunctionDeclaration(ident, ..) if ident == name);
+
+        self.declarations
+            .iter()
+            .find(|node| function(node))
+            .map(|node| (None, node))
+            .or_else(|| {
+                self.imported
+                    .iter()
+                    .find(|(_, node)| function(node))
+                    .map(|(file, node)| (Some(file), node))
+            })
+    }
+
+    fn find_variable(&self, name: &str) -> Option<(Option<&Url>, &AstNode)> {
+        let variable =
+            |node: &AstNode| matches!(node, AstNode::Assignment((ident, _), ..) if ident == name);
+
+        self.declarations
+            .iter()
+            .rev()
+            .find(|node| variable(node))
+            .map(|node| (None, node))
+            .or_else(|| {
+                self.imported
+                    .iter()
+                    .rev()
+                    .find(|(_, node)| variable(node))
+                    .map(|(file, node)| (Some(file), node))
+            })
+    }
+}
+
+/// The files a file brings in with `use`
+#[derive(Debug, Default)]
+pub struct Imports {
+    /// Every file brought in, directly or through another file, after the files it uses
+    pub files: Vec<Url>,
+    /// The `use` statements of the file that can't be followed, with the reason
+    pub errors: Vec<(String, Span)>,
+    /// Latest revisions any of the files changed at, see [`File`]
+    changed_at: u64,
+    tokens_changed_at: u64,
+}
+
+/// What the declarations of a file say about the type of each name
+#[derive(Debug, Default, PartialEq)]
+pub struct Types {
+    /// Type of the value each name produces, `None` for a variable without annotation
+    pub values: HashMap<String, Option<NumberType>>,
+    /// Type of each parameter of each function
+    pub parameters: HashMap<String, Vec<NumberType>>,
+}
+
+/// The open documents and the analyses derived from them. Every derived query is
+/// memoized per file and only recomputed once one of its inputs changed, a result equal
+/// to the previous one keeps its age so the queries depending on it stay valid too
+#[derive(Debug, Default)]
+pub struct Database {
+    state: RwLock<State>,
+    /// Revision of the latest edit, shared with the snapshots so they see they are stale
+    current: Arc<AtomicU64>,
+    /// Files that aren't open but are imported, read from disk the first time, `None`
+    /// when there is no such file
+    disk: Arc<Mutex<HashMap<Url, Option<File>>>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    revision: u64,
+    files: HashMap<Url, File>,
+    /// Workspace folders `use` paths are relative to
+    roots: Vec<PathBuf>,
+    /// Configuration of the workspace folders that have one
+    configs: HashMap<PathBuf, Arc<Config>>,
+    /// Revision of the last change to the configurations
+    configured_at: u64,
+}
+
+#[derive(Debug, Clone)]
+struct File {
+    document: Arc<Document>,
+    /// Revision of the last edit of the document
+    changed_at: u64,
+    /// Revision of the last edit that changed a token, an edit to whitespace or a
+    /// comment doesn't change what the file evaluates to
+    tokens_changed_at: u64,
+    memos: Arc<Memos>,
+}
+
+impl Default for File {
+    fn default() -> Self {
+        Self {
+            document: Arc::new(Document::new(String::new(), 0)),
+            changed_at: 0,
+            tokens_changed_at: 0,
+            memos: Arc::default(),
+        }
+    }
+}
+
+type Slot<T> = Mutex<Option<Memo<T>>>;
+
+#[derive(Debug, Default)]
+struct Memos {
+    tokens: Slot<Vec<TokenInfo>>,
+    parsed: Slot<Vec<AstNode>>,
+    ast: Slot<Vec<AstNode>>,
+    symbols: Slot<Symbols>,
+    types: Slot<Types>,
+    diagnostics: Slot<Vec<Diagnostic>>,
+    evaluation: Slot<Evaluation>,
+}
+
+#[derive(Debug)]
+struct Memo<T> {
+    value: Arc<T>,
+    /// Revision at which the value last changed
+    changed_at: u64,
+    /// Latest revision the value is known to be up to date at
+    verified_at: u64,
+}
+
+impl Database {
+    pub fn open(&self, file: Url, text: String, version: i32) {
+        let mut state = self.state.write().unwrap();
+        let revision = self.bump(&mut state);
+
+        state.files.insert(
+            file,
+            File {
+                document: Arc::new(Document::new(text, version)),
+                changed_at: revision,
+                tokens_changed_at: revision,
+                memos: Arc::default(),
+            },
+        );
+    }
+
+    pub fn edit(&self, file: &Url, changes: &[TextDocumentContentChangeEvent], version: i32) {
+        let mut state = self.state.write().unwrap();
+        let revision = self.bump(&mut state);
+
+        let file = state.files.entry(file.clone()).or_default();
+
+        // Only copied when a snapshot still holds the document, a cancelled request
+        // drops it as soon as it notices
+        let document = Arc::make_mut(&mut file.document);
+
+        let mut changed = false;
+
+        for change in changes {
+            changed |= document.edit(change, version);
+        }
+
+        file.changed_at = revision;
+
+        if changed {
+            file.tokens_changed_at = revision;
+        }
+    }
+
+    pub fn close(&self, file: &Url) {
+        let mut state = self.state.write().unwrap();
+
+        self.bump(&mut state);
+        state.files.remove(file);
+
+        // It may have been saved, the files importing it see it as it is on disk
+        self.reload(&mut state, file);
+    }
+
+    pub fn roots(&self) -> Vec<PathBuf> {
+        self.state.read().unwrap().roots.clone()
+    }
+
+    pub fn set_roots(&self, roots: Vec<PathBuf>) {
+        let mut state = self.state.write().unwrap();
+
+        self.bump(&mut state);
+        state.configs.retain(|root, _| roots.contains(root));
+        state.configured_at = state.revision;
+        state.roots = roots;
+
+        self.disk.lock().unwrap().clear();
+        Self::invalidate(&mut state);
+    }
+
+    /// Sets the configuration of the workspace folder `root`, read from its
+    /// `calcagebra.toml`
+    pub fn set_config(&self, root: PathBuf, config: Config) {
+        let mut state = self.state.write().unwrap();
+
+        self.bump(&mut state);
+        state.configs.insert(root, Arc::new(config));
+        state.configured_at = state.revision;
+
+        // Files read from disk are parsed with the configuration too
+        self.disk.lock().unwrap().clear();
+        Self::invalidate(&mut state);
+    }
+
+    /// Forgets what was read of `file` from disk, after it changed there
+    pub fn changed_on_disk(&self, file: &Url) {
+        let mut state = self.state.write().unwrap();
+
+        self.bump(&mut state);
+        self.reload(&mut state, file);
+    }
+
+    fn reload(&self, state: &mut State, file: &Url) {
+        self.disk.lock().unwrap().remove(file);
+
+        // Which files exist isn't tracked per query, any of them may be importing it
+        Self::invalidate(state);
+    }
+
+    /// Makes every query recompute, for changes the revisions of the files don't capture
+    fn invalidate(state: &mut State) {
+        for file in state.files.values_mut() {
+            file.changed_at = state.revision;
+            file.tokens_changed_at = state.revision;
+        }
+    }
+
+    /// The documents as they are now, queries on the snapshot are cancelled by the next
+    /// edit
+    pub fn snapshot(&self) -> Snapshot {
+        let state = self.state.read().unwrap();
+
+        Snapshot {
+            revision: state.revision,
+            current: self.current.clone(),
+            files: state.files.clone(),
+            disk: self.disk.clone(),
+            roots: state.roots.clone(),
+            configs: state.configs.clone(),
+            configured_at: state.configured_at,
+        }
+    }
+
+    fn bump(&self, state: &mut State) -> u64 {
+        state.revision += 1;
+        self.current.store(state.revision, Ordering::Release);
+
+        state.revision
+    }
+}
+
+/// The documents at one revision, queries run on a snapshot without blocking edits
+#[derive(Debug)]
+pub struct Snapshot {
+    revision: u64,
+    current: Arc<AtomicU64>,
+    files: HashMap<Url, File>,
+    disk: Arc<Mutex<HashMap<Url, Option<File>>>>,
+    roots: Vec<PathBuf>,
+    configs: HashMap<PathBuf, Arc<Config>>,
+    configured_at: u64,
+}
+
+impl Snapshot {
+    pub fn is_cancelled(&self) -> bool {
+        self.current.load(Ordering::Acquire) != self.revision
+    }
+
+    /// Bails out of the query if a newer edit arrived
+    pub fn unwind_if_cancelled(&self) -> Cancellable<()> {
+        if self.is_cancelled() {
+            Err(Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The open documents
+    pub fn files(&self) -> impl Iterator<Item = &Url> {
+        self.files.keys()
+    }
+
+    /// The document of `file`, read from disk if it isn't open and empty if it doesn't
+    /// exist
+    pub fn document(&self, file: &Url) -> Cancellable<Arc<Document>> {
+        self.unwind_if_cancelled()?;
+
+        Ok(self.file(file).document)
+    }
+
+    /// The configuration of the workspace folder holding `file`, the default one outside
+    /// of any workspace folder
+    pub fn config(&self, file: &Url) -> Arc<Config> {
+        file.to_file_path()
+            .ok()
+            .and_then(|path| self.configs.get(self.root(&path)?).cloned())
+            .unwrap_or_default()
+    }
+
+    pub fn tokens(&self, file: &Url) -> Cancellable<Arc<Vec<TokenInfo>>> {
+        Ok(self.tokens_memo(file)?.0)
+    }
+
+    /// The statements of the file, with the types of function signatures written without
+    /// them inferred
+    pub fn ast(&self, file: &Url) -> Cancellable<Arc<Vec<AstNode>>> {
+        Ok(self.ast_memo(file)?.0)
+    }
+
+    pub fn symbols(&self, file: &Url) -> Cancellable<Arc<Symbols>> {
+        Ok(self.symbols_memo(file)?.0)
+    }
+
+    pub fn types(&self, file: &Url) -> Cancellable<Arc<Types>> {
+        let (symbols, changed_at) = self.symbols_memo(file)?;
+
+        let (types, _) = self.memoized(&self.file(file).memos.types, changed_at, || {
+            let mut types = Types::default();
+
+            // The file's own declarations come last and override the imported ones
+            for node in symbols.iter() {
+                match node {
+                    AstNode::Assignment((ident, datatype), ..) => {
+                        types.values.insert(ident.clone(), *datatype);
+                    }
+                    AstNode::FunctionDeclaration(name, args, return_type, ..) => {
+                        types.values.insert(name.clone(), Some(*return_type));
+                        types.parameters.insert(
+                            name.clone(),
+                            args.iter().map(|(_, r#type)| *r#type).collect(),
+                        );
+                    }
+                    _ => {}
+                }
+            }
+
+            Ok(types)
+        })?;
+
+        Ok(types)
+    }
+
+    /// The errors and warnings of the parser and the `use` statements that can't be
+    /// followed, placed in the document
+    pub fn diagnostics(&self, file: &Url) -> Cancellable<Arc<Vec<Diagnostic>>> {
+        let File {
+            document,
+            changed_at,
+            memos,
+            ..
+        } = self.file(file);
+
+        let (ast, _) = self.ast_memo(file)?;
+        let imports = self.imports(file)?;
+        let config = self.config(file);
+
+        // Positions depend on the text, not only on the offsets in the AST
+        let inputs = changed_at.max(imports.changed_at);
+
+        let (diagnostics, _) = self.memoized(&memos.diagnostics, inputs, || {
+            let index = LineIndex::new(document.text());
+            let mut items = vec![];
+
+            for (message, span) in &imports.errors {
+                items.push(Diagnostic {
+                    range: Range::new(index.position(*span.start()), index.position(*span.end())),
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    message: message.to_string(),
+                    source: Some("calcagebra".to_string()),
+                    ..Default::default()
+                });
+            }
+
+            let recursion = Recursion::new(&ast);
+
+            for node in ast.iter().chain(&recursion.warnings) {
+                self.unwind_if_cancelled()?;
+
+                let severity = match node {
+                    AstNode::Warning(lint, ..) => match config.level(*lint) {
+                        Level::Allow => continue,
+                        Level::Warn => DiagnosticSeverity::WARNING,
+                        Level::Deny => DiagnosticSeverity::ERROR,
+                    },
+                    _ => DiagnosticSeverity::ERROR,
+                };
+
+                if let AstNode::Error(message, range) | AstNode::Warning(_, message, range) = node {
+                    let (start, end) = range.clone().into_inner();
+
+                    items.push(Diagnostic {
+                        range: Range::new(index.position(start), index.position(end)),
+                        severity: Some(severity),
+                        message: message.to_string(),
+                        source: Some("calcagebra".to_string()),
+                        ..Default::default()
+                    })
+                }
+            }
+
+            Ok(items)
+        })?;
+
+        Ok(diagnostics)
+    }
+
+    /// The value of every statement of the file, aligned with its AST. The imported files
+    /// run before it
+    pub fn evaluation(&self, file: &Url) -> Cancellable<Arc<Evaluation>> {
+        let File {
+            tokens_changed_at,
+            memos,
+            ..
+        } = self.file(file);
+
+        let (ast, _) = self.ast_memo(file)?;
+        let imports = self.imports(file)?;
+
+        let imported = imports
+            .files
+            .iter()
+            .map(|file| self.ast(file))
+            .collect::<Cancellable<Vec<_>>>()?;
+
+        let inputs = tokens_changed_at.max(imports.tokens_changed_at);
+
+        let (evaluation, _) = self.memoized(&memos.evaluation, inputs, || {
+            let mut evaluator = self.evaluator(file, &ast, &imported)?;
+
+            ast.iter()
+                .map(|node| {
+                    self.unwind_if_cancelled()?;
+
+                    Ok(evaluator.run(node))
+                })
+                .collect()
+        })?;
+
+        Ok(evaluation)
+    }
+
+    /// The value of `expr`, part of the statement at `index` in the AST of `file`, with the
+    /// variables the statements before it assign
+    pub fn evaluate(
+        &self,
+        file: &Url,
+        index: usize,
+        expr: &Expression,
+    ) -> Cancellable<Result<Value, EvalError>> {
+        let ast = self.ast(file)?;
+
+        let imported = self
+            .imports(file)?
+            .files
+            .iter()
+            .map(|file| self.ast(file))
+            .collect::<Cancellable<Vec<_>>>()?;
+
+        let mut evaluator = self.evaluator(file, &ast, &imported)?;
+
+        for node in ast.iter().take(index) {
+            self.unwind_if_cancelled()?;
+
+            evaluator.run(node);
+        }
+
+        Ok(evaluator.eval(expr))
+    }
+
+    /// The value `name` holds once the file has run
+    pub fn value(&self, file: &Url, name: &str) -> Cancellable<Option<Value>> {
+        // A variable the file doesn't assign has the value the file declaring it gives it
+        if let Some((Some(imported), AstNode::Assignment(..))) = self.symbols(file)?.lookup(name) {
+            return self.value(imported, name);
+        }
+
+        let ast = self.ast(file)?;
+
+        let assignment = ast
+            .iter()
+            .rposition(|f| matches!(f, AstNode::Assignment((ident, _), ..) if ident == name));
+
+        let value = match assignment {
+            Some(i) => self.evaluation(file)?[i].clone().and_then(Result::ok),
+            None => None,
+        };
+
+        Ok(value
+            .or_else(|| self.config(file).constants.get(name).cloned())
+            .or_else(|| constant(name)))
+    }
+
+    /// The files `file` brings in with `use`, cycles and missing files are reported
+    /// instead of followed
+    pub fn imports(&self, file: &Url) -> Cancellable<Imports> {
+        let mut imports = Imports::default();
+
+        for (path, span, target) in self.uses(file)? {
+            let Some(target) = target else {
+                imports
+                    .errors
+                    .push((format!("Import Error: cannot find `{path}`"), span));
+
+                continue;
+            };
+
+            if let Some(cycle) = self.chain(&target, file)? {
+                let names = [file]
+                    .into_iter()
+                    .chain(&cycle)
+                    .map(|file| format!("`{}`", name(file)))
+                    .collect::<Vec<String>>();
+
+                imports.errors.push((
+                    format!("Import Error: import cycle {}", names.join(" → ")),
+                    span,
+                ));
+
+                continue;
+            }
+
+            self.visit(&target, &mut vec![], &mut imports)?;
+        }
+
+        Ok(imports)
+    }
+
+    /// The file a `use` of `path` in `file` refers to. Relative paths start at the
+    /// workspace folder holding `file`, or next to it outside of any workspace folder,
+    /// and then at the include folders of its configuration
+    pub fn resolve(&self, file: &Url, path: &str) -> Option<Url> {
+        let current = file.to_file_path().ok()?;
+        let root = self.root(&current);
+
+        let base = root.or(current.parent())?;
+
+        let includes = root.map_or(vec![], |root| {
+            self.config(file)
+                .include
+                .iter()
+                .map(|include| root.join(include))
+                .collect()
+        });
+
+        let candidates = [base.to_path_buf()]
+            .into_iter()
+            .chain(includes)
+            .filter_map(|folder| Url::from_file_path(normalize(&folder.join(path))).ok())
+            .collect::<Vec<Url>>();
+
+        // A file found nowhere is reported at the first place it was looked up
+        candidates
+            .iter()
+            .find(|target| self.load(target).is_some())
+            .or(candidates.first())
+            .cloned()
+    }
+
+    /// The deepest workspace folder holding `path`
+    fn root(&self, path: &Path) -> Option<&Path> {
+        self.roots
+            .iter()
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.components().count())
+            .map(PathBuf::as_path)
+    }
+
+    /// The paths of the `use` statements of `file`, where they are and the file they
+    /// resolve to if it exists
+    fn uses(&self, file: &Url) -> Cancellable<Vec<(String, Span, Option<Url>)>> {
+        Ok(self
+            .parsed_memo(file)?
+            .0
+            .iter()
+            .filter_map(|node| match node {
+                AstNode::Use(path, spans) => {
+                    let target = self
+                        .resolve(file, path)
+                        .filter(|target| self.load(target).is_some());
+
+                    Some((path.clone(), spans.name.clone(), target))
+                }
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Adds `file` and what it uses to `imports`, each file after the ones it uses.
+    /// `stack` holds the files being visited so a cycle further down stops there
+    fn visit(&self, file: &Url, stack: &mut Vec<Url>, imports: &mut Imports) -> Cancellable<()> {
+        if imports.files.contains(file) || stack.contains(file) {
+            return Ok(());
+        }
+
+        stack.push(file.clone());
+
+        for (.., target) in self.uses(file)? {
+            if let Some(target) = target {
+                self.visit(&target, stack, imports)?;
+            }
+        }
+
+        stack.pop();
+
+        let loaded = self.file(file);
+
+        imports.changed_at = imports.changed_at.max(loaded.changed_at);
+        imports.tokens_changed_at = imports.tokens_changed_at.max(loaded.tokens_changed_at);
+        imports.files.push(file.clone());
+
+        Ok(())
+    }
+
+    /// The files `from` goes through to import `to`, ending with `to`
+    fn chain(&self, from: &Url, to: &Url) -> Cancellable<Option<Vec<Url>>> {
+        let mut seen = vec![];
+        let mut paths = vec![vec![from.clone()]];
+
+        while !paths.is_empty() {
+            let mut next = vec![];
+
+            for path in paths {
+                let last = path.last().unwrap();
+
+                if last == to {
+                    return Ok(Some(path));
+                }
+
+                if seen.contains(last) {
+                    continue;
+                }
+
+                seen.push(last.clone());
+
+                for (.., target) in self.uses(last)? {
+                    if let Some(target) = target {
+                        next.push([path.clone(), vec![target]].concat());
+                    }
+                }
+            }
+
+            paths = next;
+        }
+
+        Ok(None)
+    }
+
+    /// An evaluator knowing the functions of `file` and of the files it uses, with the
+    /// used files already run
+    fn evaluator(
+        &self,
+        file: &Url,
+        ast: &[AstNode],
+        imported: &[Arc<Vec<AstNode>>],
+    ) -> Cancellable<Evaluator> {
+        let mut evaluator = Evaluator::with_constants(self.config(file).constants.clone());
+
+        for node in imported.iter().flat_map(|ast| ast.iter()).chain(ast) {
+            if let AstNode::FunctionDeclaration(..) = node {
+                evaluator.run(node);
+            }
+        }
+
+        for node in imported.iter().flat_map(|ast| ast.iter()) {
+            self.unwind_if_cancelled()?;
+
+            evaluator.run(node);
+        }
+
+        Ok(evaluator)
+    }
+
+    fn file(&self, file: &Url) -> File {
+        self.load(file).unwrap_or_default()
+    }
+
+    /// The open document of `file`, or else what is on disk
+    fn load(&self, file: &Url) -> Option<File> {
+        if let Some(file) = self.files.get(file) {
+            return Some(file.clone());
+        }
+
+        self.disk
+            .lock()
+            .unwrap()
+            .entry(file.clone())
+            .or_insert_with(|| {
+                let text = fs::read_to_string(file.to_file_path().ok()?).ok()?;
+
+                Some(File {
+                    document: Arc::new(Document::new(text, 0)),
+                    changed_at: self.revision,
+                    tokens_changed_at: self.revision,
+                    memos: Arc::default(),
+                })
+            })
+            .clone()
+    }
+
+    fn tokens_memo(&self, file: &Url) -> Cancellable<(Arc<Vec<TokenInfo>>, u64)> {
+        let File {
+            document,
+            changed_at,
+            memos,
+            ..
+        } = self.file(file);
+
+        self.memoized(&memos.tokens, changed_at, || {
+            Ok(document.tokens().cloned().collect())
+        })
+    }
+
+    /// The statements as the parser reads them, what `use` statements there are doesn't
+    /// depend on other files
+    fn parsed_memo(&self, file: &Url) -> Cancellable<(Arc<Vec<AstNode>>, u64)> {
+        let File {
+            document,
+            changed_at,
+            memos,
+            ..
+        } = self.file(file);
+
+        self.memoized(&memos.parsed, changed_at, || {
+            Ok(document.ast().cloned().collect())
+        })
+    }
+
+    fn ast_memo(&self, file: &Url) -> Cancellable<(Arc<Vec<AstNode>>, u64)> {
+        let (parsed, changed_at) = self.parsed_memo(file)?;
+        let imports = self.imports(file)?;
+
+        // Calls to imported functions take the signatures inferred in their files
+        let imported = imports
+            .files
+            .iter()
+            .map(|file| self.ast(file))
+            .collect::<Cancellable<Vec<_>>>()?;
+
+        // The parse keeps its age when the configuration changes, not the types it infers
+        let config = self.config(file);
+        let inputs = changed_at.max(imports.changed_at).max(self.configured_at);
+
+        self.memoized(&self.file(file).memos.ast, inputs, || {
+            Ok(inference::infer(
+                &parsed,
+                imported.iter().flat_map(|ast| ast.iter()),
+                &config,
+            ))
+        })
+    }
+
+    fn symbols_memo(&self, file: &Url) -> Cancellable<(Arc<Symbols>, u64)> {
+        let (ast, changed_at) = self.ast_memo(file)?;
+        let imports = self.imports(file)?;
+
+        let imported = imports
+            .files
+            .iter()
+            .map(|file| Ok((file, self.ast(file)?)))
+            .collect::<Cancellable<Vec<_>>>()?;
+
+        let declaration = |node: &&AstNode| {
+            matches!(
+                node,
+                AstNode::Assignment(..) | AstNode::FunctionDeclaration(..)
+            )
+        };
+
+        let inputs = changed_at.max(imports.changed_at);
+
+        self.memoized(&self.file(file).memos.symbols, inputs, || {
+            Ok(Symbols {
+                declarations: ast.iter().filter(declaration).cloned().collect(),
+                imported: imported
+                    .iter()
+                    .flat_map(|(file, ast)| {
+                        ast.iter()
+                            .filter(declaration)
+                            .map(|node| ((*file).clone(), node.clone()))
+                    })
+                    .collect(),
+            })
+        })
+    }
+
+    /// Returns the memoized value of a query with the revision it last changed at,
+    /// recomputing it if one of its inputs changed after it was last verified
+    fn memoized<T: PartialEq>(
+        &self,
+        slot: &Slot<T>,
+        inputs: u64,
+        compute: impl FnOnce() -> Cancellable<T>,
+    ) -> Cancellable<(Arc<T>, u64)> {
+        let mut memo = slot.lock().unwrap();
+
+        self.unwind_if_cancelled()?;
+
+        if let Some(memo) = memo.as_mut() {
+            // A newer snapshot already went through, this one is stale
+            if memo.verified_at > self.revision {
+                return Err(Cancelled);
+            }
+
+            if inputs <= memo.verified_at {
+                memo.verified_at = self.revision;
+
+                return Ok((memo.value.clone(), memo.changed_at));
+            }
+        }
+
+        let value = compute()?;
+
+        let (value, changed_at) = match memo.take() {
+            Some(old) if *old.value == value => (old.value, old.changed_at),
+            _ => (Arc::new(value), self.revision),
+        };
+
+        *memo = Some(Memo {
+            value: value.clone(),
+            changed_at,
+            verified_at: self.revision,
+        });
+
+        Ok((value, changed_at))
+    }
+}
+
+/// The name of a file, as shown in messages
+pub fn name(file: &Url) -> &str {
+    file.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .unwrap_or(file.as_str())
+}
+
+/// Removes the `.` and `..` of a path without touching the file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
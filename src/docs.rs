@@ -0,0 +1,252 @@
+use crate::{parser::Parser, standardlibrary::internal_type_map, token::Token, types::NumberType};
+
+/// Documentation parsed from the `///` comments above a declaration
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Docs {
+    pub summary: String,
+    pub params: Vec<(String, String)>,
+    pub returns: Option<String>,
+    pub examples: Vec<String>,
+}
+
+impl Docs {
+    /// Splits a doc comment into its summary, `@param name description`,
+    /// `@return description` and `@example` sections. Example sections run until
+    /// the next tag
+    pub fn parse(doc: &str) -> Self {
+        let mut docs = Self::default();
+        let mut summary = vec![];
+        let mut example: Option<Vec<&str>> = None;
+
+        for line in doc.lines() {
+            let trimmed = line.trim();
+
+            if trimmed.starts_with('@') {
+                if let Some(example) = example.take() {
+                    docs.examples.push(example.join("\n"));
+                }
+            } else if let Some(example) = example.as_mut() {
+                example.push(line);
+                continue;
+            }
+
+            if let Some(param) = trimmed.strip_prefix("@param") {
+                let (name, description) = param
+                    .trim()
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((param.trim(), ""));
+
+                docs.params
+                    .push((name.to_string(), description.trim().to_string()));
+            } else if let Some(returns) = trimmed
+                .strip_prefix("@returns")
+                .or_else(|| trimmed.strip_prefix("@return"))
+            {
+                docs.returns = Some(returns.trim().to_string());
+            } else if let Some(code) = trimmed.strip_prefix("@example") {
+                example = Some(if code.trim().is_empty() {
+                    vec![]
+                } else {
+                    vec![code.trim()]
+                });
+            } else {
+                summary.push(line);
+            }
+        }
+
+        if let Some(example) = example {
+            docs.examples.push(example.join("\n"));
+        }
+
+        docs.summary = summary.join("\n").trim().to_string();
+
+        docs
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, description)| description.as_str())
+    }
+
+    /// Renders the documentation as Markdown below a code-fenced signature
+    pub fn markdown(&self, signature: &str) -> String {
+        let body = self.body();
+
+        if body.is_empty() {
+            fence(signature)
+        } else {
+            format!("{}\n\n{body}", fence(signature))
+        }
+    }
+
+    /// Renders the documentation as Markdown without a signature
+    pub fn body(&self) -> String {
+        let mut sections = vec![];
+
+        if !self.summary.is_empty() {
+            sections.push(self.summary.to_string());
+        }
+
+        if !self.params.is_empty() {
+            sections.push(format!(
+                "**Parameters**\n{}",
+                self.params
+                    .iter()
+                    .map(|(name, description)| format!("- `{name}` — {description}"))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            ));
+        }
+
+        if let Some(returns) = &self.returns {
+            sections.push(format!("**Returns** {returns}"));
+        }
+
+        for example in &self.examples {
+            sections.push(format!("**Example**\n{}", fence(example)));
+        }
+
+        sections.join("\n\n")
+    }
+}
+
+pub fn fence(code: &str) -> String {
+    format!("```calcagebra\n{code}\n```")
+}
+
+pub fn signature(name: &str, args: &[(String, NumberType)], return_type: &NumberType) -> String {
+    format!("fn {name}{}", detail(args, return_type))
+}
+
+/// The part of a signature after the function name, `(x: R): R`
+pub fn detail(args: &[(String, NumberType)], return_type: &NumberType) -> String {
+    format!(
+        "({}): {return_type}",
+        args.iter()
+            .map(|(name, r#type)| format!("{name}: {type}"))
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
+
+pub fn std_signature(name: &str) -> String {
+    format!("fn {name}{}", std_detail(name))
+}
+
+pub fn std_detail(name: &str) -> String {
+    format!(
+        "({}): {}",
+        std_params(name).join(", "),
+        internal_type_map(name).1
+    )
+}
+
+/// Parameters of a STD function, written as the types each one accepts
+pub fn std_params(name: &str) -> Vec<String> {
+    internal_type_map(name)
+        .0
+        .iter()
+        .map(|arg| {
+            arg.iter()
+                .map(|r#type| r#type.to_string())
+                .collect::<Vec<String>>()
+                .join("|")
+        })
+        .collect()
+}
+
+/// Markdown explaining an operator or keyword, shown when hovering tokens is enabled
+pub fn token_docs(token: &Token) -> Option<String> {
+    let description = match token {
+        Token::Add => "Addition, element-wise for matrices of the same size",
+        Token::Sub => "Subtraction, or negation when written before a number",
+        Token::Mul => {
+            "Multiplication, the matrix product for two matrices. Implied between a number and \
+             a name, `2x` is `2 * x`"
+        }
+        Token::Div => "Division, truncating when both sides are in `Z`",
+        Token::Pow => "Exponentiation, `a ^ b` raises `a` to the power `b`",
+        Token::Rem => "Remainder of the division of the left side by the right side",
+        Token::IsEq => "Equality, a `Bool` that is true when both sides are equal",
+        Token::NEq => "Inequality, a `Bool` that is true when the sides differ",
+        Token::Gt => "Greater than, a `Bool`",
+        Token::Lt => "Less than, a `Bool`",
+        Token::GtEq => "Greater than or equal, a `Bool`",
+        Token::LtEq => "Less than or equal, a `Bool`",
+        Token::Belongs => "Belongs, `x E Z` is true when `x` is a member of the number set",
+        Token::Abs => {
+            "Absolute value, `|x|` is the magnitude of a number in `Z` or `R`, the modulus of a \
+             number in `C` and the determinant of a `Matrix`"
+        }
+        Token::Eq => "Binds the name on the left to the value on the right",
+        Token::Colon => "Introduces the type of a name or of a function's result",
+        Token::Let => "Declares a variable, `let name: Type = value`",
+        Token::Fn => "Declares a function, `fn name(x: Type): Type = body`",
+        Token::Use => {
+            "Brings the functions and variables of another file in, `use \"path.cal\"` with \
+             the path relative to the workspace folder"
+        }
+        Token::If | Token::Then | Token::Else | Token::End => {
+            "Conditional, `if condition then value else value end` picks the first value when \
+             the `Bool` condition is true"
+        }
+        _ => return None,
+    };
+
+    let (left, right) = Parser::infix_binding_power(token);
+
+    // `if` blocks are parsed as expressions of their own, their binding power never applies
+    if left == 0 || matches!(token, Token::If | Token::Then | Token::Else | Token::End) {
+        return Some(format!("`{token}` — {description}"));
+    }
+
+    // A right binding power above the left one stops a following operator of the same
+    // precedence from taking the right operand, making the operator left-associative
+    let associativity = if right > left {
+        "left-associative"
+    } else {
+        "right-associative"
+    };
+
+    Some(format!(
+        "`{token}` — {description}\n\nBinding power {left}, higher binds tighter, {associativity}"
+    ))
+}
+
+/// Markdown describing a number type and how its values are promoted
+pub fn type_docs(r#type: NumberType) -> String {
+    let description = match r#type {
+        NumberType::Natural => {
+            "Natural numbers `0, 1, 2, …`, a refinement of `Z` whose values are stored as `Z`"
+        }
+        NumberType::Rational => {
+            "Rational numbers, a refinement of `R` whose values are stored as `R`. Every finite \
+             `R` value is a ratio of integers, so `x E Q` holds for them"
+        }
+        NumberType::Int => {
+            "Integers, 32-bit. Promoted to `R` when combined with a real number and to `C` when \
+             combined with a complex number"
+        }
+        NumberType::Real => {
+            "Real numbers, 32-bit floating point. Integers are promoted to `R`, and `R` is \
+             promoted to `C` when combined with a complex number"
+        }
+        NumberType::Complex => {
+            "Complex numbers `a + bi`. Any operation with a `C` operand promotes the other \
+             operand to `C`"
+        }
+        NumberType::Matrix => {
+            "Matrices written `[a, b; c, d]`. Scalars are applied to every element and `|M|` \
+             is the determinant"
+        }
+        NumberType::Bool => {
+            "Truth values produced by comparisons and `E`. Conditions of `if` must be `Bool`, \
+             and `Bool` values are only compared with `==` and `!=`"
+        }
+        NumberType::Unknown => "A type that could not be inferred",
+    };
+
+    format!("{}\n\n{description}", fence(&r#type.to_string()))
+}
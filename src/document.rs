@@ -0,0 +1,460 @@
+use tower_lsp::lsp_types::{Position, TextDocumentContentChangeEvent};
+
+use crate::{
+    ast::AstNode,
+    lexer::Lexer,
+    parser::Parser,
+    position,
+    syntax::SyntaxNode,
+    token::{Token, TokenInfo},
+};
+
+/// An open document split into chunks of whole lines that parse the same on their own as
+/// in the document. An edit only reparses the chunks it touches, the others are moved to
+/// their new offsets
+#[derive(Debug, Clone)]
+pub struct Document {
+    version: i32,
+    text: String,
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Clone)]
+struct Chunk {
+    lines: usize,
+    bytes: usize,
+    /// Width in lexer offsets, every line counts its chars and one for its terminator
+    len: usize,
+    tokens: Vec<TokenInfo>,
+    ast: Vec<AstNode>,
+}
+
+impl Chunk {
+    /// Parses the text of a chunk starting at lexer offset `start`
+    fn parse(text: &str, start: usize) -> Self {
+        let by = start as isize - 1;
+
+        let mut tokens = Lexer::new(text).tokens();
+        let mut ast = Parser::new(&SyntaxNode::parse(text)).partial_ast();
+
+        tokens.iter_mut().for_each(|tokeninfo| tokeninfo.shift(by));
+        ast.iter_mut().for_each(|node| node.shift(by));
+
+        Self {
+            lines: text.split_inclusive('\n').count(),
+            bytes: text.len(),
+            len: text.lines().map(|line| line.chars().count() + 1).sum(),
+            tokens,
+            ast,
+        }
+    }
+
+    fn shift(&mut self, by: isize) {
+        self.tokens
+            .iter_mut()
+            .for_each(|tokeninfo| tokeninfo.shift(by));
+        self.ast.iter_mut().for_each(|node| node.shift(by));
+    }
+}
+
+impl Document {
+    pub fn new(text: String, version: i32) -> Self {
+        let (chunks, _) = chunks(&text, 1);
+
+        Self {
+            version,
+            text,
+            chunks,
+        }
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn tokens(&self) -> impl DoubleEndedIterator<Item = &TokenInfo> {
+        self.chunks.iter().flat_map(|chunk| &chunk.tokens)
+    }
+
+    pub fn ast(&self) -> impl Iterator<Item = &AstNode> + Clone {
+        self.chunks.iter().flat_map(|chunk| &chunk.ast)
+    }
+
+    /// The token covering the char at `offset`
+    pub fn token_at(&self, offset: usize) -> Option<&TokenInfo> {
+        let mut start = 1;
+
+        for chunk in &self.chunks {
+            if offset < start + chunk.len {
+                return position::token_at(&chunk.tokens, offset);
+            }
+
+            start += chunk.len;
+        }
+
+        None
+    }
+
+    /// The text from the start of the chunk holding `position` up to it, enough to tell
+    /// which statement the position is in
+    pub fn before(&self, position: Position) -> &str {
+        let (start, _) = self.line(position.line as usize);
+
+        &self.text[start..self.byte(position)]
+    }
+
+    /// Applies an edit from the client, returns whether it changed any token. Edits to
+    /// whitespace and comments only move the tokens after them
+    pub fn edit(&mut self, change: &TextDocumentContentChangeEvent, version: i32) -> bool {
+        let Some(range) = change.range else {
+            *self = Self::new(change.text.clone(), version);
+            return true;
+        };
+
+        let start = self.byte(range.start);
+        let end = self.byte(range.end);
+
+        let mut text = std::mem::take(&mut self.text);
+
+        text.replace_range(start..end, &change.text);
+
+        if self.chunks.is_empty() {
+            *self = Self::new(text, version);
+            return true;
+        }
+
+        self.text = text;
+        self.version = version;
+
+        let first = self.chunk(range.start.line as usize);
+        let mut last = self.chunk(range.end.line as usize).max(first);
+
+        let grown = change.text.len() as isize - (end - start) as isize;
+
+        let region = self.chunks[..first].iter().map(|c| c.bytes).sum::<usize>();
+        let offset = 1 + self.chunks[..first].iter().map(|c| c.len).sum::<usize>();
+
+        // Reparse until the new chunks end where an old one did, everything after still
+        // parses the same and only has to move
+        let reparsed = loop {
+            let bytes = self.chunks[first..=last]
+                .iter()
+                .map(|c| c.bytes)
+                .sum::<usize>();
+
+            let (chunks, clean) = chunks(
+                &self.text[region..(region + bytes).saturating_add_signed(grown)],
+                offset,
+            );
+
+            if clean || last + 1 == self.chunks.len() {
+                break chunks;
+            }
+
+            last += 1;
+        };
+
+        let old = first..last + 1;
+
+        let unchanged = self.chunks[old.clone()]
+            .iter()
+            .flat_map(|chunk| &chunk.tokens)
+            .map(|t| &t.token)
+            .eq(reparsed
+                .iter()
+                .flat_map(|chunk| &chunk.tokens)
+                .map(|t| &t.token));
+
+        let moved = reparsed.iter().map(|c| c.len).sum::<usize>() as isize
+            - self.chunks[old.clone()]
+                .iter()
+                .map(|c| c.len)
+                .sum::<usize>() as isize;
+
+        let after = first + reparsed.len();
+
+        self.chunks.splice(old, reparsed);
+
+        for chunk in &mut self.chunks[after..] {
+            chunk.shift(moved);
+        }
+
+        !unchanged
+    }
+
+    /// Index of the chunk holding `line`, the last one past the end of the document
+    fn chunk(&self, line: usize) -> usize {
+        let mut start = 0;
+
+        for (i, chunk) in self.chunks.iter().enumerate() {
+            if line < start + chunk.lines {
+                return i;
+            }
+
+            start += chunk.lines;
+        }
+
+        self.chunks.len().saturating_sub(1)
+    }
+
+    /// Byte offset of the chunk holding `line`, and of the line itself
+    fn line(&self, line: usize) -> (usize, usize) {
+        let mut start = 0;
+        let mut first = 0;
+
+        for chunk in &self.chunks {
+            if line < first + chunk.lines {
+                break;
+            }
+
+            start += chunk.bytes;
+            first += chunk.lines;
+        }
+
+        let line_start = self.text[start..]
+            .split_inclusive('\n')
+            .take(line - first)
+            .map(str::len)
+            .sum::<usize>();
+
+        (start, start + line_start)
+    }
+
+    /// Byte offset of an LSP position, clamped to the end of its line
+    fn byte(&self, position: Position) -> usize {
+        let (_, mut byte) = self.line(position.line as usize);
+        let mut units = 0;
+
+        for char in self.text[byte..].chars() {
+            if units >= position.character as usize || char == '\n' || char == '\r' {
+                break;
+            }
+
+            units += char.len_utf16();
+            byte += char.len_utf8();
+        }
+
+        byte
+    }
+}
+
+/// Splits `text`, starting at lexer offset `start`, into chunks and parses them. A line
+/// ends a chunk when no bracket, `if` block or block comment is open after it and no doc
+/// comment waits for a statement, as then the lines below parse the same whatever is
+/// above. Also returns whether the last line could end a chunk
+fn chunks(text: &str, mut start: usize) -> (Vec<Chunk>, bool) {
+    let lexer = Lexer::new(text);
+    let open = lexer.open_comments();
+    let tokens = lexer.tokens();
+
+    let mut ends = vec![];
+    let mut depth = 0usize;
+    let mut doc = false;
+    let mut code = false;
+
+    for (i, tokeninfo) in tokens.iter().enumerate() {
+        code |= !matches!(tokeninfo.token, Token::Newline | Token::DocComment(_));
+
+        match tokeninfo.token {
+            Token::LParen | Token::LSquare | Token::LCurly | Token::If => depth += 1,
+            Token::RParen | Token::RSquare | Token::RCurly | Token::End => {
+                depth = depth.saturating_sub(1)
+            }
+            Token::DocComment(_) if depth == 0 => doc = true,
+            Token::Newline => {
+                let declaration = tokens
+                    .get(i + 1)
+                    .is_some_and(|t| matches!(t.token, Token::Let | Token::Fn | Token::Use));
+
+                ends.push(depth == 0 && !doc && !open[ends.len()]);
+
+                // The statement ends here, see `Parser`
+                if declaration {
+                    depth = 0;
+                }
+
+                // Doc comments belong to the next statement, over blank and comment lines
+                if code {
+                    doc = false;
+                }
+
+                code = false;
+            }
+            _ => {}
+        }
+    }
+
+    let mut chunks = vec![];
+    let mut from = 0;
+    let mut to = 0;
+
+    for (line, end) in text.split_inclusive('\n').zip(&ends) {
+        to += line.len();
+
+        if *end || to == text.len() {
+            let chunk = Chunk::parse(&text[from..to], start);
+
+            start += chunk.len;
+            from = to;
+
+            chunks.push(chunk);
+        }
+    }
+
+    (chunks, ends.last().is_none_or(|end| *end))
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Range;
+
+    use super::*;
+
+    const FRAGMENTS: [&str; 30] = [
+        "let ",
+        "fn ",
+        "x",
+        "f(x)",
+        " = ",
+        "1",
+        "2.5",
+        "2x",
+        "i",
+        "\n",
+        "\n",
+        "\n",
+        "(",
+        ")",
+        "[1, 2; 3, 4]",
+        "[",
+        "]",
+        ";",
+        "if x > 1 then ",
+        " else ",
+        " end",
+        "/*",
+        "*/",
+        "// c",
+        "/// doc\n",
+        "fn f(a) = a + 1\n",
+        "let y = x * 2\n",
+        "π",
+        "+",
+        "\"s",
+    ];
+
+    /// xorshift, enough to pick edits reproducibly
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    /// A byte offset of `text` at a char boundary, never between `\r` and `\n`
+    fn offset(text: &str, random: &mut Random) -> usize {
+        let offsets = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([text.len()])
+            .filter(|i| !(text[..*i].ends_with('\r') && text[*i..].starts_with('\n')))
+            .collect::<Vec<usize>>();
+
+        offsets[random.below(offsets.len())]
+    }
+
+    fn position(text: &str, offset: usize) -> Position {
+        let before = &text[..offset];
+        let line = before.matches('\n').count();
+        let start = before.rfind('\n').map_or(0, |i| i + 1);
+
+        Position::new(line as u32, before[start..].encode_utf16().count() as u32)
+    }
+
+    fn assert_reparsed(document: &Document, edits: &[String]) {
+        let text = document.text();
+
+        assert_eq!(
+            document.tokens().cloned().collect::<Vec<TokenInfo>>(),
+            Lexer::new(text).tokens(),
+            "tokens of {text:?} after {edits:?}"
+        );
+        let ast = document.ast().cloned().collect::<Vec<AstNode>>();
+        let full = Parser::new(&SyntaxNode::parse(text)).partial_ast();
+
+        for (a, b) in ast.iter().zip(&full) {
+            assert_eq!(a, b, "ast of {text:?} after {edits:?}");
+        }
+
+        assert_eq!(ast.len(), full.len(), "ast of {text:?} after {edits:?}");
+    }
+
+    fn fuzz(newline: &str, seed: u64) {
+        let mut random = Random(seed);
+
+        let source = "/// One\nlet x = 1\nfn f(a) = if a > 1 then\n  a\nelse\n  0\nend\n\nlet m = [1, 2;\n 3, 4]\n/* block\n */ let y = f(x)\n"
+            .replace('\n', newline);
+
+        let mut document = Document::new(source.clone(), 0);
+        let mut expected = source;
+        let mut edits = vec![];
+
+        for version in 1..=300 {
+            let start = offset(&expected, &mut random);
+            let end = match random.below(3) {
+                0 => start,
+                _ => offset(&expected, &mut random).max(start),
+            };
+
+            let end = if expected[start..end].ends_with('\r') {
+                end + 1
+            } else {
+                end
+            };
+
+            let text = match random.below(4) {
+                0 => String::new(),
+                _ => FRAGMENTS[random.below(FRAGMENTS.len())].replace('\n', newline),
+            };
+
+            let change = TextDocumentContentChangeEvent {
+                range: Some(Range::new(
+                    position(&expected, start),
+                    position(&expected, end),
+                )),
+                range_length: None,
+                text: text.clone(),
+            };
+
+            edits.push(format!("{start}..{end} {text:?}"));
+            expected.replace_range(start..end, &text);
+            document.edit(&change, version);
+
+            assert_eq!(document.text(), expected, "after {edits:?}");
+            assert_reparsed(&document, &edits);
+        }
+    }
+
+    #[test]
+    fn incremental_reparse_matches_full_parse() {
+        for seed in 1..=20 {
+            fuzz("\n", seed * 7919);
+        }
+    }
+
+    #[test]
+    fn incremental_reparse_matches_full_parse_crlf() {
+        for seed in 1..=20 {
+            fuzz("\r\n", seed * 104729);
+        }
+    }
+}
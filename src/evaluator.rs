@@ -0,0 +1,1108 @@
+use std::{
+    collections::HashMap,
+    f32::consts::{E, PI},
+    fmt::Display,
+};
+
+use crate::{
+    ast::{AstNode, Expression},
+    token::Token,
+    types::NumberType,
+};
+
+/// Maximum nesting of user function calls before evaluation is aborted
+const MAX_DEPTH: usize = 128;
+
+/// Default number of expressions a single evaluation may visit
+pub const MAX_STEPS: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i32),
+    Real(f32),
+    Complex(f32, f32),
+    Matrix(Vec<Vec<Value>>),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    DivisionByZero,
+    Domain(String),
+    Overflow,
+    RecursionLimit,
+    StepLimit,
+    Unresolved(String),
+    Type(String),
+    Invalid,
+}
+
+impl Value {
+    pub fn datatype(&self) -> NumberType {
+        match self {
+            Value::Int(_) => NumberType::Int,
+            Value::Real(_) => NumberType::Real,
+            Value::Complex(..) => NumberType::Complex,
+            Value::Matrix(_) => NumberType::Matrix,
+            Value::Bool(_) => NumberType::Bool,
+        }
+    }
+}
+
+pub struct Evaluator {
+    variables: HashMap<String, Value>,
+    /// Constants of the workspace, in scope unless a variable hides them
+    constants: HashMap<String, Value>,
+    functions: HashMap<String, (Vec<String>, Expression)>,
+    scopes: Vec<HashMap<String, Value>>,
+    depth: usize,
+    steps: usize,
+    max_steps: usize,
+}
+
+impl Evaluator {
+    pub fn new() -> Self {
+        Self {
+            variables: HashMap::new(),
+            constants: HashMap::new(),
+            functions: HashMap::new(),
+            scopes: vec![],
+            depth: 0,
+            steps: 0,
+            max_steps: MAX_STEPS,
+        }
+    }
+
+    pub fn with_constants(constants: HashMap<String, Value>) -> Self {
+        Self {
+            constants,
+            ..Self::new()
+        }
+    }
+
+    /// Runs a single statement, returning the value it produced if it is an expression
+    /// or an assignment
+    pub fn run(&mut self, node: &AstNode) -> Option<Result<Value, EvalError>> {
+        match node {
+            AstNode::Assignment((name, _), expr, ..) => {
+                let value = self.eval(expr);
+
+                match &value {
+                    Ok(v) => self.variables.insert(name.to_string(), v.clone()),
+                    Err(_) => self.variables.remove(name),
+                };
+
+                Some(value)
+            }
+            AstNode::FunctionDeclaration(name, args, _, body, ..) => {
+                self.functions.insert(
+                    name.to_string(),
+                    (
+                        args.iter().map(|(arg, _)| arg.to_string()).collect(),
+                        body.clone(),
+                    ),
+                );
+
+                None
+            }
+            AstNode::FunctionCall(name, args, spans) => Some(self.eval(&Expression::FunctionCall(
+                name.to_string(),
+                args.clone(),
+                spans.node.clone(),
+            ))),
+            AstNode::Use(..) | AstNode::Error(..) | AstNode::Warning(..) => None,
+        }
+    }
+
+    pub fn variable(&self, name: &str) -> Option<Value> {
+        self.variables
+            .get(name)
+            .or_else(|| self.constants.get(name))
+            .cloned()
+            .or_else(|| constant(name))
+    }
+
+    pub fn eval(&mut self, expr: &Expression) -> Result<Value, EvalError> {
+        self.steps = 0;
+        self.depth = 0;
+        self.scopes.clear();
+
+        self.expression(expr)
+    }
+
+    fn expression(&mut self, expr: &Expression) -> Result<Value, EvalError> {
+        self.charge(1)?;
+
+        match expr {
+            Expression::Abs(expr, _) => {
+                let value = self.expression(expr)?;
+                self.charge(cost("abs", std::slice::from_ref(&value)))?;
+                abs(value)
+            }
+            Expression::Binary(lhs, Token::Belongs, rhs, _) => {
+                let Expression::Identifier(set, _) = &**rhs else {
+                    return Err(EvalError::Type("`E` expects a number set".to_string()));
+                };
+
+                let value = self.expression(lhs)?;
+
+                belongs(value, set)
+            }
+            Expression::Binary(lhs, op, rhs, _) => {
+                let lhs = self.expression(lhs)?;
+                let rhs = self.expression(rhs)?;
+
+                self.charge(cost(operation(op), &[lhs.clone(), rhs.clone()]))?;
+
+                apply(lhs, op, rhs)
+            }
+            Expression::Branched(condition, then, otherwise, _) => {
+                if truthy(&self.expression(condition)?)? {
+                    self.expression(then)
+                } else {
+                    self.expression(otherwise)
+                }
+            }
+            Expression::Identifier(ident, _) => self.lookup(ident),
+            Expression::Integer(n, _) => n.to_i32().map(Value::Int).ok_or(EvalError::Overflow),
+            Expression::Real(n, _) => finite(n.to_f32()),
+            Expression::Imaginary(n, _) => imaginary_literal(n.to_f32()),
+            Expression::Matrix(rows, _) => {
+                let mut matrix = vec![];
+
+                for row in rows {
+                    let mut values = vec![];
+
+                    for expr in row {
+                        values.push(self.expression(expr)?);
+                    }
+
+                    matrix.push(values);
+                }
+
+                Ok(Value::Matrix(matrix))
+            }
+            Expression::FunctionCall(name, args, _) => {
+                let mut values = vec![];
+
+                for arg in args {
+                    values.push(self.expression(arg)?);
+                }
+
+                self.call(name, values)
+            }
+            Expression::Error(_) => Err(EvalError::Invalid),
+        }
+    }
+
+    fn lookup(&self, ident: &str) -> Result<Value, EvalError> {
+        if let Some(value) = self.scopes.last().and_then(|scope| scope.get(ident)) {
+            return Ok(value.clone());
+        }
+
+        self.variable(ident)
+            .ok_or_else(|| EvalError::Unresolved(ident.to_string()))
+    }
+
+    fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, EvalError> {
+        let Some((params, body)) = self.functions.get(name).cloned() else {
+            self.charge(cost(name, &args))?;
+            return call_std(name, args);
+        };
+
+        if params.len() != args.len() {
+            return Err(EvalError::Type(format!(
+                "`{name}` expects {} arguments, found {}",
+                params.len(),
+                args.len()
+            )));
+        }
+
+        if self.depth >= MAX_DEPTH {
+            return Err(EvalError::RecursionLimit);
+        }
+
+        self.depth += 1;
+        self.scopes.push(params.into_iter().zip(args).collect());
+
+        let value = self.expression(&body);
+
+        self.scopes.pop();
+        self.depth -= 1;
+
+        value
+    }
+
+    /// Counts `steps` more against the budget of the evaluation
+    fn charge(&mut self, steps: usize) -> Result<(), EvalError> {
+        self.steps = self.steps.saturating_add(steps);
+
+        if self.steps > self.max_steps {
+            return Err(EvalError::StepLimit);
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn constant(name: &str) -> Option<Value> {
+    match name {
+        "pi" | "π" => Some(Value::Real(PI)),
+        "e" => Some(Value::Real(E)),
+        _ => None,
+    }
+}
+
+/// Applies a binary operator, promoting operands along Z -> R -> C
+pub fn apply(lhs: Value, op: &Token, rhs: Value) -> Result<Value, EvalError> {
+    match (lhs, rhs) {
+        (Value::Bool(a), Value::Bool(b)) if matches!(op, Token::IsEq | Token::NEq) => {
+            Ok(boolean(if *op == Token::IsEq { a == b } else { a != b }))
+        }
+        (Value::Bool(_), _) | (_, Value::Bool(_)) => {
+            Err(EvalError::Type(format!("`{op}` is not defined for Bool")))
+        }
+        (Value::Matrix(a), Value::Matrix(b)) => match op {
+            Token::Add | Token::Sub => {
+                if a.len() != b.len() || a.iter().zip(&b).any(|(x, y)| x.len() != y.len()) {
+                    return Err(EvalError::Type(
+                        "matrices must have the same dimensions".to_string(),
+                    ));
+                }
+
+                let mut matrix = vec![];
+
+                for (x, y) in a.into_iter().zip(b) {
+                    let mut row = vec![];
+
+                    for (x, y) in x.into_iter().zip(y) {
+                        row.push(apply(x, op, y)?);
+                    }
+
+                    matrix.push(row);
+                }
+
+                Ok(Value::Matrix(matrix))
+            }
+            Token::Mul => multiply(&a, &b),
+            Token::IsEq | Token::NEq => {
+                let eq = Value::Matrix(a) == Value::Matrix(b);
+                Ok(boolean(if *op == Token::IsEq { eq } else { !eq }))
+            }
+            _ => Err(EvalError::Type(format!(
+                "`{op}` is not defined for matrices"
+            ))),
+        },
+        (Value::Matrix(a), scalar) if matches!(op, Token::Mul | Token::Div) => {
+            map(a, |x| apply(x, op, scalar.clone()))
+        }
+        (scalar, Value::Matrix(b)) if *op == Token::Mul => map(b, |x| apply(scalar.clone(), op, x)),
+        (Value::Matrix(_), _) | (_, Value::Matrix(_)) => Err(EvalError::Type(format!(
+            "`{op}` is not defined between a matrix and a scalar"
+        ))),
+        (Value::Int(a), Value::Int(b)) => int(a, op, b),
+        (a @ Value::Complex(..), b) | (a, b @ Value::Complex(..)) => {
+            complex(complex_parts(&a)?, op, complex_parts(&b)?)
+        }
+        (a, b) => real(to_real(&a)?, op, to_real(&b)?),
+    }
+}
+
+fn int(a: i32, op: &Token, b: i32) -> Result<Value, EvalError> {
+    let n = match op {
+        Token::Add => a.checked_add(b),
+        Token::Sub => a.checked_sub(b),
+        Token::Mul => a.checked_mul(b),
+        Token::Div | Token::Rem if b == 0 => return Err(EvalError::DivisionByZero),
+        Token::Div => a.checked_div(b),
+        Token::Rem => a.checked_rem(b),
+        Token::Pow if b < 0 => return real(a as f32, op, b as f32),
+        Token::Pow => a.checked_pow(b as u32),
+        _ => return compare(a.partial_cmp(&b), op),
+    };
+
+    n.map(Value::Int).ok_or(EvalError::Overflow)
+}
+
+fn real(a: f32, op: &Token, b: f32) -> Result<Value, EvalError> {
+    let n = match op {
+        Token::Add => a + b,
+        Token::Sub => a - b,
+        Token::Mul => a * b,
+        Token::Div | Token::Rem if b == 0.0 => return Err(EvalError::DivisionByZero),
+        Token::Div => a / b,
+        Token::Rem => a % b,
+        Token::Pow => a.powf(b),
+        _ => return compare(a.partial_cmp(&b), op),
+    };
+
+    finite(n)
+}
+
+fn complex((a, b): (f32, f32), op: &Token, (c, d): (f32, f32)) -> Result<Value, EvalError> {
+    let (re, im) = match op {
+        Token::Add => (a + c, b + d),
+        Token::Sub => (a - c, b - d),
+        Token::Mul => (a * c - b * d, a * d + b * c),
+        Token::Div => {
+            let denominator = c * c + d * d;
+
+            if denominator == 0.0 {
+                return Err(EvalError::DivisionByZero);
+            }
+
+            ((a * c + b * d) / denominator, (b * c - a * d) / denominator)
+        }
+        Token::Pow => {
+            if a == 0.0 && b == 0.0 {
+                return Ok(Value::Real(if c == 0.0 && d == 0.0 { 1.0 } else { 0.0 }));
+            }
+
+            let (modulus, argument) = ((a * a + b * b).sqrt().ln(), b.atan2(a));
+            let (re, im) = (c * modulus - d * argument, d * modulus + c * argument);
+
+            (re.exp() * im.cos(), re.exp() * im.sin())
+        }
+        Token::IsEq => return Ok(boolean(a == c && b == d)),
+        Token::NEq => return Ok(boolean(a != c || b != d)),
+        _ => {
+            return Err(EvalError::Type(format!(
+                "`{op}` is not defined for complex numbers"
+            )));
+        }
+    };
+
+    if !re.is_finite() || !im.is_finite() {
+        return Err(EvalError::Overflow);
+    }
+
+    if im == 0.0 {
+        Ok(Value::Real(re))
+    } else {
+        Ok(Value::Complex(re, im))
+    }
+}
+
+fn compare(ordering: Option<std::cmp::Ordering>, op: &Token) -> Result<Value, EvalError> {
+    let Some(ordering) = ordering else {
+        return Err(EvalError::Domain("comparison with NaN".to_string()));
+    };
+
+    Ok(boolean(match op {
+        Token::IsEq => ordering.is_eq(),
+        Token::NEq => ordering.is_ne(),
+        Token::Gt => ordering.is_gt(),
+        Token::Lt => ordering.is_lt(),
+        Token::GtEq => ordering.is_ge(),
+        Token::LtEq => ordering.is_le(),
+        _ => return Err(EvalError::Type(format!("`{op}` is not a binary operator"))),
+    }))
+}
+
+/// Steps a builtin takes on `args` besides the call itself, the matrix routines take one
+/// for each entry they visit
+pub fn cost(name: &str, args: &[Value]) -> usize {
+    let size = |value: Option<&Value>| match value {
+        Some(Value::Matrix(rows)) => (rows.len(), rows.first().map_or(0, Vec::len)),
+        _ => (0, 0),
+    };
+
+    let (rows, columns) = size(args.first());
+
+    match name {
+        "determinant" | "inverse" | "abs" => rows.saturating_pow(3),
+        // A determinant by elimination for each cofactor
+        "adj" => rows.saturating_pow(5),
+        "transpose" | "add" | "sub" => rows * columns,
+        "mul" => (rows * columns).saturating_mul(size(args.get(1)).1),
+        _ => 0,
+    }
+}
+
+/// Name of the builtin doing what the operator `op` does
+pub fn operation(op: &Token) -> &'static str {
+    match op {
+        Token::Add => "add",
+        Token::Sub => "sub",
+        Token::Mul => "mul",
+        Token::Div => "div",
+        Token::Pow => "pow",
+        Token::Rem => "rem",
+        Token::IsEq => "is_eq",
+        Token::NEq => "neq",
+        Token::Gt => "gt",
+        Token::GtEq => "gteq",
+        Token::Lt => "lt",
+        Token::LtEq => "lteq",
+        _ => "",
+    }
+}
+
+pub fn call_std(name: &str, args: Vec<Value>) -> Result<Value, EvalError> {
+    let arity = match name {
+        "read" => 0,
+        "add" | "sub" | "mul" | "div" | "pow" | "rem" | "is_eq" | "neq" | "gt" | "gteq" | "lt"
+        | "lteq" | "log" | "nrt" => 2,
+        _ => 1,
+    };
+
+    if args.len() != arity {
+        return Err(EvalError::Type(format!(
+            "`{name}` expects {arity} arguments, found {}",
+            args.len()
+        )));
+    }
+
+    let mut args = args.into_iter();
+    let x = args.next();
+    let y = args.next();
+
+    let unary = |f: fn(f32) -> f32| finite(f(to_real(x.as_ref().unwrap())?));
+
+    match name {
+        "read" | "graph" => Err(EvalError::Unresolved(format!("{name}()"))),
+        "print" => Ok(x.unwrap()),
+        "int" => {
+            let n = to_real(x.as_ref().unwrap())?.trunc();
+
+            if n < i32::MIN as f32 || n > i32::MAX as f32 {
+                return Err(EvalError::Overflow);
+            }
+
+            Ok(Value::Int(n as i32))
+        }
+        "real" => Ok(Value::Real(to_real(x.as_ref().unwrap())?)),
+        "add" | "sub" | "mul" | "div" | "pow" | "rem" | "is_eq" | "neq" | "gt" | "gteq" | "lt"
+        | "lteq" => {
+            let op = match name {
+                "add" => Token::Add,
+                "sub" => Token::Sub,
+                "mul" => Token::Mul,
+                "div" => Token::Div,
+                "pow" => Token::Pow,
+                "rem" => Token::Rem,
+                "is_eq" => Token::IsEq,
+                "neq" => Token::NEq,
+                "gt" => Token::Gt,
+                "gteq" => Token::GtEq,
+                "lt" => Token::Lt,
+                _ => Token::LtEq,
+            };
+
+            apply(x.unwrap(), &op, y.unwrap())
+        }
+        "abs" => abs(x.unwrap()),
+        "re" => Ok(Value::Real(complex_parts(&x.unwrap())?.0)),
+        "im" => Ok(Value::Real(complex_parts(&x.unwrap())?.1)),
+        "conj" => {
+            let (re, im) = complex_parts(&x.unwrap())?;
+            complex((re, -im), &Token::Add, (0.0, 0.0))
+        }
+        "arg" => {
+            let (re, im) = complex_parts(&x.unwrap())?;
+            finite(im.atan2(re))
+        }
+        "round" => unary(f32::round),
+        "ceil" => unary(f32::ceil),
+        "floor" => unary(f32::floor),
+        "sin" => unary(f32::sin),
+        "cos" => unary(f32::cos),
+        "tan" => unary(f32::tan),
+        "cbrt" => unary(f32::cbrt),
+        "sqrt" => {
+            if to_real(x.as_ref().unwrap())? < 0.0 {
+                return Err(EvalError::Domain("sqrt of a negative number".to_string()));
+            }
+
+            unary(f32::sqrt)
+        }
+        "ln" | "log10" => {
+            if to_real(x.as_ref().unwrap())? <= 0.0 {
+                return Err(EvalError::Domain(format!(
+                    "{name} of a non-positive number"
+                )));
+            }
+
+            unary(if name == "ln" { f32::ln } else { f32::log10 })
+        }
+        "log" => {
+            let (n, base) = (to_real(&x.unwrap())?, to_real(&y.unwrap())?);
+
+            if n <= 0.0 {
+                return Err(EvalError::Domain(
+                    "log of a non-positive number".to_string(),
+                ));
+            }
+
+            if base == 1.0 {
+                return Err(EvalError::Domain("log with base 1".to_string()));
+            }
+
+            if base <= 0.0 {
+                return Err(EvalError::Domain(
+                    "log with a non-positive base".to_string(),
+                ));
+            }
+
+            finite(n.log(base))
+        }
+        "nrt" => {
+            let (n, root) = (to_real(&x.unwrap())?, to_real(&y.unwrap())?);
+
+            if root == 0.0 {
+                return Err(EvalError::DivisionByZero);
+            }
+
+            if n < 0.0 && root % 2.0 == 0.0 {
+                return Err(EvalError::Domain(
+                    "even root of a negative number".to_string(),
+                ));
+            }
+
+            finite(n.signum() * n.abs().powf(1.0 / root))
+        }
+        "transpose" => Ok(Value::Matrix(transpose(matrix(x.unwrap())?))),
+        "determinant" => determinant(&matrix(x.unwrap())?),
+        "adj" => adjugate(&matrix(x.unwrap())?).map(Value::Matrix),
+        "inverse" => inverse(&matrix(x.unwrap())?),
+        _ => Err(EvalError::Unresolved(format!("{name}()"))),
+    }
+}
+
+/// Whether `value` is a member of the number set named `set`, `1` when it is and `0`
+/// otherwise
+pub fn belongs(value: Value, set: &str) -> Result<Value, EvalError> {
+    let Some(set) = NumberType::parse(set).filter(|set| set.is_number_set()) else {
+        return Err(EvalError::Type(format!("`{set}` is not a number set")));
+    };
+
+    let (re, im) = match value {
+        Value::Int(n) => (n as f32, 0.0),
+        Value::Real(n) => (n, 0.0),
+        Value::Complex(re, im) => (re, im),
+        Value::Matrix(_) | Value::Bool(_) => {
+            return Err(EvalError::Type(
+                "`E` expects a number on its left".to_string(),
+            ));
+        }
+    };
+
+    let real = im == 0.0 && re.is_finite();
+
+    Ok(boolean(match set {
+        NumberType::Natural => real && re.fract() == 0.0 && re >= 0.0,
+        NumberType::Int => real && re.fract() == 0.0,
+        // Every finite floating point number is a ratio of integers
+        NumberType::Rational | NumberType::Real => real,
+        _ => re.is_finite() && im.is_finite(),
+    }))
+}
+
+pub fn abs(value: Value) -> Result<Value, EvalError> {
+    match value {
+        Value::Int(n) => n.checked_abs().map(Value::Int).ok_or(EvalError::Overflow),
+        Value::Real(n) => Ok(Value::Real(n.abs())),
+        Value::Complex(a, b) => finite((a * a + b * b).sqrt()),
+        Value::Matrix(m) => determinant(&m),
+        Value::Bool(_) => Err(EvalError::Type("`|` is not defined for Bool".to_string())),
+    }
+}
+
+fn map(
+    matrix: Vec<Vec<Value>>,
+    f: impl Fn(Value) -> Result<Value, EvalError>,
+) -> Result<Value, EvalError> {
+    let mut result = vec![];
+
+    for row in matrix {
+        let mut values = vec![];
+
+        for value in row {
+            values.push(f(value)?);
+        }
+
+        result.push(values);
+    }
+
+    Ok(Value::Matrix(result))
+}
+
+fn multiply(a: &[Vec<Value>], b: &[Vec<Value>]) -> Result<Value, EvalError> {
+    let columns = b.first().map(|row| row.len()).unwrap_or(0);
+
+    if a.iter().any(|row| row.len() != b.len()) || b.iter().any(|row| row.len() != columns) {
+        return Err(EvalError::Type(
+            "matrix dimensions do not allow multiplication".to_string(),
+        ));
+    }
+
+    let mut result = vec![];
+
+    for row in a {
+        let mut values = vec![];
+
+        for j in 0..columns {
+            let mut sum = Value::Int(0);
+
+            for (value, column) in row.iter().zip(b) {
+                let product = apply(value.clone(), &Token::Mul, column[j].clone())?;
+                sum = apply(sum, &Token::Add, product)?;
+            }
+
+            values.push(sum);
+        }
+
+        result.push(values);
+    }
+
+    Ok(Value::Matrix(result))
+}
+
+fn transpose(matrix: Vec<Vec<Value>>) -> Vec<Vec<Value>> {
+    let columns = matrix.first().map(|row| row.len()).unwrap_or(0);
+
+    (0..columns)
+        .map(|j| {
+            matrix
+                .iter()
+                .filter_map(|row| row.get(j).cloned())
+                .collect()
+        })
+        .collect()
+}
+
+fn square(matrix: &[Vec<Value>]) -> Result<(), EvalError> {
+    if matrix.is_empty() || matrix.iter().any(|row| row.len() != matrix.len()) {
+        return Err(EvalError::Type("matrix must be square".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Matrix with row `i` and column `j` removed
+fn minor(matrix: &[Vec<Value>], i: usize, j: usize) -> Vec<Vec<Value>> {
+    matrix
+        .iter()
+        .enumerate()
+        .filter(|(r, _)| *r != i)
+        .map(|(_, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(c, _)| *c != j)
+                .map(|(_, value)| value.clone())
+                .collect()
+        })
+        .collect()
+}
+
+fn cofactor(matrix: &[Vec<Value>], i: usize, j: usize) -> Result<Value, EvalError> {
+    let minor = determinant(&minor(matrix, i, j))?;
+
+    if (i + j).is_multiple_of(2) {
+        Ok(minor)
+    } else {
+        apply(Value::Int(0), &Token::Sub, minor)
+    }
+}
+
+/// The matrix with its integers made real, elimination divides them
+fn real_entries(matrix: &[Vec<Value>]) -> Vec<Vec<Value>> {
+    matrix
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|value| match value {
+                    Value::Int(n) => Value::Real(*n as f32),
+                    value => value.clone(),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The row from `k` down with the largest entry in column `k`, none when they are all zero
+fn pivot(rows: &[Vec<Value>], k: usize) -> Result<Option<usize>, EvalError> {
+    let mut pivot = None;
+    let mut largest = 0.0;
+
+    for (i, row) in rows.iter().enumerate().skip(k) {
+        let magnitude = to_real(&abs(row[k].clone())?)?;
+
+        if magnitude > largest {
+            pivot = Some(i);
+            largest = magnitude;
+        }
+    }
+
+    Ok(pivot)
+}
+
+/// Subtracts `factor` times row `k` from row `i`
+fn eliminate(rows: &mut [Vec<Value>], i: usize, k: usize, factor: Value) -> Result<(), EvalError> {
+    for j in 0..rows[i].len() {
+        let product = apply(factor.clone(), &Token::Mul, rows[k][j].clone())?;
+        rows[i][j] = apply(rows[i][j].clone(), &Token::Sub, product)?;
+    }
+
+    Ok(())
+}
+
+/// Determinant by Gaussian elimination. An integer matrix has an integer determinant, it
+/// is rounded to drop the error of the divisions
+fn determinant(matrix: &[Vec<Value>]) -> Result<Value, EvalError> {
+    if matrix.is_empty() {
+        return Ok(Value::Int(1));
+    }
+
+    square(matrix)?;
+
+    let integer = matrix
+        .iter()
+        .flatten()
+        .all(|value| matches!(value, Value::Int(_)));
+    let mut rows = real_entries(matrix);
+    let mut determinant = Value::Real(1.0);
+
+    for k in 0..rows.len() {
+        let Some(pivot) = pivot(&rows, k)? else {
+            return Ok(Value::Real(0.0));
+        };
+
+        if pivot != k {
+            rows.swap(pivot, k);
+            determinant = apply(Value::Int(0), &Token::Sub, determinant)?;
+        }
+
+        determinant = apply(determinant, &Token::Mul, rows[k][k].clone())?;
+
+        for i in k + 1..rows.len() {
+            let factor = apply(rows[i][k].clone(), &Token::Div, rows[k][k].clone())?;
+            eliminate(&mut rows, i, k, factor)?;
+        }
+    }
+
+    match determinant {
+        Value::Real(n) if integer => finite(n.round()),
+        determinant => Ok(determinant),
+    }
+}
+
+fn adjugate(matrix: &[Vec<Value>]) -> Result<Vec<Vec<Value>>, EvalError> {
+    square(matrix)?;
+
+    let mut cofactors = vec![];
+
+    for i in 0..matrix.len() {
+        let mut row = vec![];
+
+        for j in 0..matrix.len() {
+            row.push(cofactor(matrix, i, j)?);
+        }
+
+        cofactors.push(row);
+    }
+
+    Ok(transpose(cofactors))
+}
+
+/// Inverse by Gauss-Jordan elimination of the matrix next to the identity, which turns
+/// into the inverse as the matrix turns into the identity
+fn inverse(matrix: &[Vec<Value>]) -> Result<Value, EvalError> {
+    square(matrix)?;
+
+    let n = matrix.len();
+    let mut rows = real_entries(matrix);
+
+    for (i, row) in rows.iter_mut().enumerate() {
+        row.extend((0..n).map(|j| Value::Real(if i == j { 1.0 } else { 0.0 })));
+    }
+
+    for k in 0..n {
+        let Some(pivot) = pivot(&rows, k)? else {
+            return Err(EvalError::Domain(
+                "inverse of a singular matrix".to_string(),
+            ));
+        };
+
+        rows.swap(pivot, k);
+
+        let pivot = rows[k][k].clone();
+
+        for value in &mut rows[k] {
+            *value = apply(value.clone(), &Token::Div, pivot.clone())?;
+        }
+
+        for i in (0..n).filter(|i| *i != k) {
+            let factor = rows[i][k].clone();
+            eliminate(&mut rows, i, k, factor)?;
+        }
+    }
+
+    Ok(Value::Matrix(
+        rows.into_iter().map(|row| row[n..].to_vec()).collect(),
+    ))
+}
+
+fn matrix(value: Value) -> Result<Vec<Vec<Value>>, EvalError> {
+    match value {
+        Value::Matrix(m) => Ok(m),
+        value => Err(EvalError::Type(format!("expected a matrix, found {value}"))),
+    }
+}
+
+/// Value of an imaginary literal, a coefficient past the range of f32 overflows
+pub fn imaginary_literal(im: f32) -> Result<Value, EvalError> {
+    finite(im).map(|_| Value::Complex(0.0, im))
+}
+
+pub fn finite(n: f32) -> Result<Value, EvalError> {
+    if n.is_nan() {
+        Err(EvalError::Domain("result is not a number".to_string()))
+    } else if n.is_infinite() {
+        Err(EvalError::Overflow)
+    } else {
+        Ok(Value::Real(n))
+    }
+}
+
+fn boolean(b: bool) -> Value {
+    Value::Bool(b)
+}
+
+fn truthy(value: &Value) -> Result<bool, EvalError> {
+    match value {
+        Value::Bool(b) => Ok(*b),
+        value => Err(EvalError::Type(format!(
+            "expected a Bool condition, found {value}"
+        ))),
+    }
+}
+
+fn to_real(value: &Value) -> Result<f32, EvalError> {
+    match value {
+        Value::Int(n) => Ok(*n as f32),
+        Value::Real(n) => Ok(*n),
+        Value::Complex(re, im) if *im == 0.0 => Ok(*re),
+        value => Err(EvalError::Type(format!(
+            "expected a real number, found {value}"
+        ))),
+    }
+}
+
+fn complex_parts(value: &Value) -> Result<(f32, f32), EvalError> {
+    match value {
+        Value::Complex(re, im) => Ok((*re, *im)),
+        value => Ok((to_real(value)?, 0.0)),
+    }
+}
+
+fn imaginary(im: f32) -> String {
+    match im {
+        1.0 => "i".to_string(),
+        -1.0 => "-i".to_string(),
+        im => format!("{im}i"),
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{n}"),
+            Value::Real(n) => write!(f, "{n}"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Complex(re, im) if *re == 0.0 => write!(f, "{}", imaginary(*im)),
+            Value::Complex(re, im) if *im < 0.0 => write!(f, "{re}-{}", imaginary(-im)),
+            Value::Complex(re, im) => write!(f, "{re}+{}", imaginary(*im)),
+            Value::Matrix(rows) => write!(
+                f,
+                "[{}]",
+                rows.iter()
+                    .map(|row| row
+                        .iter()
+                        .map(|value| value.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", "))
+                    .collect::<Vec<String>>()
+                    .join("; ")
+            ),
+        }
+    }
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::DivisionByZero => write!(f, "division by zero"),
+            EvalError::Domain(message) => write!(f, "{message}"),
+            EvalError::Overflow => write!(f, "arithmetic overflow"),
+            EvalError::RecursionLimit => write!(f, "recursion limit of {MAX_DEPTH} reached"),
+            EvalError::StepLimit => write!(f, "evaluation step budget exhausted"),
+            EvalError::Unresolved(name) => write!(f, "`{name}` cannot be resolved"),
+            EvalError::Type(message) => write!(f, "{message}"),
+            EvalError::Invalid => write!(f, "expression contains a syntax error"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, syntax::SyntaxNode};
+
+    /// Value of the last statement of `source` producing one
+    fn eval(source: &str) -> Result<Value, EvalError> {
+        let ast = Parser::new(&SyntaxNode::parse(source)).ast().unwrap();
+        let mut evaluator = Evaluator::new();
+
+        ast.iter()
+            .filter_map(|node| evaluator.run(node))
+            .last()
+            .expect("no statement produced a value")
+    }
+
+    fn reals(value: Value) -> Vec<Vec<f32>> {
+        match value {
+            Value::Matrix(rows) => rows
+                .iter()
+                .map(|row| row.iter().map(|value| to_real(value).unwrap()).collect())
+                .collect(),
+            value => panic!("expected a matrix, found {value}"),
+        }
+    }
+
+    fn assert_close(actual: Vec<Vec<f32>>, expected: &[&[f32]]) {
+        assert_eq!(actual.len(), expected.len());
+
+        for (row, expected) in actual.iter().zip(expected) {
+            for (a, b) in row.iter().zip(*expected) {
+                assert!((a - b).abs() < 1e-5, "{actual:?} != {expected:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(eval("let x = 2 + 3 * 4"), Ok(Value::Int(14)));
+        assert_eq!(eval("let x = 7 / 2"), Ok(Value::Int(3)));
+        assert_eq!(eval("let x = 2 ^ 10"), Ok(Value::Int(1024)));
+        assert_eq!(eval("let x = 1.5 + 1"), Ok(Value::Real(2.5)));
+        assert_eq!(
+            eval("let x = (1 + 2i) * (3 - i)"),
+            Ok(Value::Complex(5.0, 5.0))
+        );
+        assert_eq!(eval("let x = 2 - 1 > 0"), Ok(Value::Bool(true)));
+    }
+
+    #[test]
+    fn arithmetic_errors() {
+        assert_eq!(
+            eval("fn f(n: Z): Z = 1 / n\nlet x = f(0)"),
+            Err(EvalError::DivisionByZero)
+        );
+        assert_eq!(
+            eval("fn f(n: Z): Z = n + 1\nlet x = f(2147483647)"),
+            Err(EvalError::Overflow)
+        );
+        assert_eq!(
+            eval("fn f(n: R): R = sqrt(n)\nlet x = f(-1)"),
+            Err(EvalError::Domain("sqrt of a negative number".to_string()))
+        );
+    }
+
+    #[test]
+    fn matrices() {
+        assert_eq!(
+            eval("let x = [1, 2; 3, 4] * [5; 6]"),
+            Ok(Value::Matrix(vec![
+                vec![Value::Int(17)],
+                vec![Value::Int(39)]
+            ]))
+        );
+        assert_eq!(
+            eval("let x = determinant([1, 2; 3, 4])"),
+            Ok(Value::Real(-2.0))
+        );
+        assert_eq!(eval("let x = abs([2, 0; 0, 3])"), Ok(Value::Real(6.0)));
+        assert_eq!(
+            eval("let x = determinant([0, 1, 2; 1, 0, 3; 4, -3, 8])"),
+            Ok(Value::Real(-2.0))
+        );
+
+        assert_close(
+            reals(eval("let x = adj([1, 2; 3, 4])").unwrap()),
+            &[&[4.0, -2.0], &[-3.0, 1.0]],
+        );
+        assert_close(
+            reals(eval("let x = inverse([1, 2; 3, 4])").unwrap()),
+            &[&[-2.0, 1.0], &[1.5, -0.5]],
+        );
+        assert_close(
+            reals(eval("let x = transpose([1, 2, 3])").unwrap()),
+            &[&[1.0], &[2.0], &[3.0]],
+        );
+
+        assert_eq!(
+            eval("fn f(m) = inverse(m)\nlet x = f([1, 2; 2, 4])"),
+            Err(EvalError::Domain(
+                "inverse of a singular matrix".to_string()
+            ))
+        );
+        assert_eq!(
+            eval("let x = determinant([1, 2])"),
+            Err(EvalError::Type("matrix must be square".to_string()))
+        );
+    }
+
+    #[test]
+    fn large_determinant_is_quick() {
+        // Cofactor expansion would take 12! steps
+        let rows = (0..12)
+            .map(|i| {
+                (0..12)
+                    .map(|j| if i == j { "2" } else { "0" })
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            })
+            .collect::<Vec<String>>()
+            .join("; ");
+
+        assert_eq!(
+            eval(&format!("let x = determinant([{rows}])")),
+            Ok(Value::Real(4096.0))
+        );
+    }
+
+    #[test]
+    fn recursion() {
+        assert_eq!(
+            eval("fn f(n: Z): Z = if n == 0 then 1 else n * f(n - 1) end\nlet x = f(10)"),
+            Ok(Value::Int(3628800))
+        );
+        assert_eq!(
+            eval("fn f(n: Z): Z = f(n + 1)\nlet x = f(0)"),
+            Err(EvalError::RecursionLimit)
+        );
+    }
+
+    #[test]
+    fn step_limit() {
+        assert_eq!(
+            eval("fn f(n: Z): Z = if n == 0 then 1 else f(n - 1) + f(n - 1) end\nlet x = f(30)"),
+            Err(EvalError::StepLimit)
+        );
+
+        // The matrix routines are charged for the entries they visit
+        let rows = vec![vec!["1"; 50].join(", "); 50].join("; ");
+
+        assert_eq!(
+            eval(&format!("let x = adj([{rows}])")),
+            Err(EvalError::StepLimit)
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use crate::{lexer::Lexer, syntax::SyntaxKind};
+
+/// Formats a document: each line is indented by `indent` once per bracket and `if`
+/// block open around it, and the tokens on it are spaced the same way everywhere. Line
+/// breaks and comments are kept as written. Returns `None` when the result wouldn't lex to
+/// the same tokens as `source`
+pub fn reformat(source: &str, indent: &str, spaced_operators: bool) -> Option<String> {
+    let tokens = Lexer::new(source).lossless_tokens();
+
+    let mut formatted = String::new();
+    let mut depth = 0usize;
+    let mut block = false;
+
+    for line in tokens.split_inclusive(|token| token.kind() == SyntaxKind::Newline) {
+        let (line, terminator) = match line.split_last() {
+            Some((last, line)) if last.kind() == SyntaxKind::Newline => (line, last.text()),
+            _ => (line, ""),
+        };
+
+        let first = line
+            .iter()
+            .map(|token| token.kind())
+            .find(|kind| *kind != SyntaxKind::Whitespace);
+
+        // A declaration ends whatever is still open, see `Parser`
+        if matches!(
+            first,
+            Some(SyntaxKind::Let | SyntaxKind::Fn | SyntaxKind::Use)
+        ) {
+            depth = 0;
+        }
+
+        let level = match first {
+            Some(
+                SyntaxKind::RParen
+                | SyntaxKind::RSquare
+                | SyntaxKind::RCurly
+                | SyntaxKind::Then
+                | SyntaxKind::Else
+                | SyntaxKind::End,
+            ) => depth.saturating_sub(1),
+            _ => depth,
+        };
+
+        // A line starting inside a block comment is kept as written
+        let verbatim = block;
+
+        if verbatim {
+            line.iter()
+                .for_each(|token| formatted.push_str(token.text()));
+        } else if first.is_some() {
+            formatted.push_str(&indent.repeat(level));
+        }
+
+        let mut previous = vec![];
+        let mut gap = false;
+
+        for token in line {
+            let kind = token.kind();
+
+            match kind {
+                SyntaxKind::Whitespace => {
+                    gap = true;
+                    continue;
+                }
+                SyntaxKind::LParen | SyntaxKind::LSquare | SyntaxKind::LCurly | SyntaxKind::If => {
+                    depth += 1
+                }
+                SyntaxKind::RParen | SyntaxKind::RSquare | SyntaxKind::RCurly | SyntaxKind::End => {
+                    depth = depth.saturating_sub(1)
+                }
+                _ => {}
+            }
+
+            if !verbatim {
+                if let Some(&prev) = previous.last()
+                    && space(
+                        previous.iter().rev().nth(1).copied(),
+                        prev,
+                        kind,
+                        gap,
+                        spaced_operators,
+                    )
+                {
+                    formatted.push(' ');
+                }
+
+                formatted.push_str(token.text());
+            }
+
+            if kind == SyntaxKind::Comment {
+                block = open_after(token.text(), block);
+            }
+
+            previous.push(kind);
+            gap = false;
+        }
+
+        formatted.push_str(terminator);
+    }
+
+    let tokens = |text: &str| {
+        Lexer::new(text)
+            .tokens()
+            .into_iter()
+            .map(|tokeninfo| tokeninfo.token)
+            .collect::<Vec<_>>()
+    };
+
+    (tokens(&formatted) == tokens(source)).then_some(formatted)
+}
+
+/// Whether a space goes between `prev` and `next`, `before` being the token before `prev`
+/// on the line and `gap` whether there was whitespace between them
+fn space(
+    before: Option<SyntaxKind>,
+    prev: SyntaxKind,
+    next: SyntaxKind,
+    gap: bool,
+    spaced_operators: bool,
+) -> bool {
+    // Operator characters next to each other lex as one token, `>-` in `x>-1`
+    if symbolic(prev) && symbolic(next) {
+        return true;
+    }
+
+    match (prev, next) {
+        (SyntaxKind::Comment | SyntaxKind::DocComment | SyntaxKind::LexError, _)
+        | (_, SyntaxKind::Comment | SyntaxKind::LexError) => gap,
+        // `E` is a letter, `xER` would be an identifier
+        (SyntaxKind::Eq | SyntaxKind::Belongs, _) | (_, SyntaxKind::Eq | SyntaxKind::Belongs) => {
+            true
+        }
+        // `|` both opens and closes an absolute value, which side it is isn't known here
+        (SyntaxKind::Abs, _) | (_, SyntaxKind::Abs) => gap,
+        (_, SyntaxKind::Comma | SyntaxKind::SemiColon | SyntaxKind::Colon) => false,
+        (_, SyntaxKind::RParen | SyntaxKind::RSquare | SyntaxKind::RCurly) => false,
+        (SyntaxKind::LParen | SyntaxKind::LSquare | SyntaxKind::LCurly, _) => false,
+        (SyntaxKind::Comma | SyntaxKind::SemiColon | SyntaxKind::Colon, _) => true,
+        (SyntaxKind::Identifier, SyntaxKind::LParen) => false,
+        (SyntaxKind::Add | SyntaxKind::Sub, _) if before == Some(SyntaxKind::Abs) => gap,
+        // A sign sticks to its operand
+        (SyntaxKind::Add | SyntaxKind::Sub, _) if before.is_none_or(starts_operand) => false,
+        _ if keyword(prev) || keyword(next) => true,
+        _ if symbolic(prev) || symbolic(next) => spaced_operators,
+        _ => gap,
+    }
+}
+
+/// Tokens made of the characters the lexer reads as one operator when they touch
+fn symbolic(kind: SyntaxKind) -> bool {
+    matches!(
+        kind,
+        SyntaxKind::Add
+            | SyntaxKind::Sub
+            | SyntaxKind::Mul
+            | SyntaxKind::Div
+            | SyntaxKind::Pow
+            | SyntaxKind::Rem
+            | SyntaxKind::Eq
+            | SyntaxKind::NEq
+            | SyntaxKind::IsEq
+            | SyntaxKind::Gt
+            | SyntaxKind::Lt
+            | SyntaxKind::GtEq
+            | SyntaxKind::LtEq
+            | SyntaxKind::Colon
+    )
+}
+
+fn keyword(kind: SyntaxKind) -> bool {
+    matches!(
+        kind,
+        SyntaxKind::Let
+            | SyntaxKind::Fn
+            | SyntaxKind::Use
+            | SyntaxKind::If
+            | SyntaxKind::Then
+            | SyntaxKind::Else
+            | SyntaxKind::End
+    )
+}
+
+/// Tokens after which `+` and `-` are signs rather than operators
+fn starts_operand(kind: SyntaxKind) -> bool {
+    symbolic(kind)
+        || matches!(
+            kind,
+            SyntaxKind::LParen
+                | SyntaxKind::LSquare
+                | SyntaxKind::LCurly
+                | SyntaxKind::Comma
+                | SyntaxKind::SemiColon
+                | SyntaxKind::If
+                | SyntaxKind::Then
+                | SyntaxKind::Else
+                | SyntaxKind::Belongs
+        )
+}
+
+/// Whether a block comment is still open after `comment`, `block` being whether one was
+/// open before it
+fn open_after(comment: &str, block: bool) -> bool {
+    let rest = match comment.strip_prefix("/*") {
+        Some(rest) if !block => rest,
+        _ if block => comment,
+        _ => return false,
+    };
+
+    !rest.ends_with("*/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCES: [&str; 10] = [
+        "let x=1+2*y",
+        "fn f(a:Z):Z=if a>1 then\na\nelse\n-a\nend",
+        "let m=[1,2;\n3,4]\nlet n = [ [1] ]",
+        "print( f( 1 , 2 ) )\ngraph(x^2 - 3x)",
+        "// c\n/// doc\nlet x = 1 /* b */ + 2   // t\n/* open\n   still */ let y=2",
+        "let x = 1\r\nfn g(b) = if b then\r\n1\r\nelse 2 end\r\n",
+        "let a=|x|-|y|\nlet b = x E R\nlet c=x>-1",
+        "use \"lib.cal\"\nlet s = read()   \n\n\nlet t=s%2==0",
+        "let p = (1 +\n2) *\n(3 - 4)\nlet q = 2.5e3x",
+        "let z = 3+2i\nlet w = -i*z\nlet u = f(-1, +2)",
+    ];
+
+    fn comments(source: &str) -> Vec<String> {
+        Lexer::new(source)
+            .lossless_tokens()
+            .into_iter()
+            .filter(|token| matches!(token.kind(), SyntaxKind::Comment | SyntaxKind::DocComment))
+            .map(|token| token.text().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn spacing() {
+        assert_eq!(
+            reformat("let x=1+2*y", "    ", true).unwrap(),
+            "let x = 1 + 2 * y"
+        );
+        assert_eq!(
+            reformat("let x=1+2*y", "    ", false).unwrap(),
+            "let x = 1+2*y"
+        );
+        assert_eq!(
+            reformat("print( f( 1 , -2 ) )", "    ", true).unwrap(),
+            "print(f(1, -2))"
+        );
+    }
+
+    #[test]
+    fn indentation() {
+        assert_eq!(
+            reformat("fn f(a:Z):Z=if a>1 then\na\nelse\n-a\nend", "  ", true).unwrap(),
+            "fn f(a: Z): Z = if a > 1 then\n  a\nelse\n  -a\nend"
+        );
+        assert_eq!(
+            reformat("let m=[1,2;\n3,4]\nlet n = 1", "\t", true).unwrap(),
+            "let m = [1, 2;\n\t3, 4]\nlet n = 1"
+        );
+    }
+
+    #[test]
+    fn keeps_comments_and_line_breaks() {
+        let source = "// c\n/// doc\nlet x = 1 /* b */ + 2   // t\n/* open\n   still */ let y=2";
+        let formatted = reformat(source, "    ", true).unwrap();
+
+        assert_eq!(
+            formatted,
+            "// c\n/// doc\nlet x = 1 /* b */ + 2 // t\n/* open\n   still */ let y=2"
+        );
+        assert_eq!(
+            reformat("let x = 1\r\nlet y=2\r\n", "    ", true).unwrap(),
+            "let x = 1\r\nlet y = 2\r\n"
+        );
+    }
+
+    #[test]
+    fn preserves_tokens_and_comments() {
+        for source in SOURCES {
+            for spaced in [true, false] {
+                let formatted = reformat(source, "    ", spaced).unwrap();
+
+                let tokens = |text: &str| {
+                    Lexer::new(text)
+                        .tokens()
+                        .into_iter()
+                        .map(|tokeninfo| tokeninfo.token)
+                        .collect::<Vec<_>>()
+                };
+
+                assert_eq!(tokens(&formatted), tokens(source), "{source:?}");
+                assert_eq!(comments(&formatted), comments(source), "{source:?}");
+                assert_eq!(
+                    formatted.lines().count(),
+                    source.lines().count(),
+                    "{source:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn idempotent() {
+        for source in SOURCES {
+            for spaced in [true, false] {
+                let formatted = reformat(source, "    ", spaced).unwrap();
+
+                assert_eq!(
+                    reformat(&formatted, "    ", spaced).as_deref(),
+                    Some(formatted.as_str()),
+                    "{source:?}"
+                );
+            }
+        }
+    }
+}
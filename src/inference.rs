@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::{AstNode, Expression},
+    config::Config,
+    evaluator,
+    standardlibrary::{STD, internal_type_map},
+    token::Token,
+    types::NumberType,
+};
+
+/// Type of an expression while inferring, a variable stands for a type not settled yet
+#[derive(Debug, Clone, Copy)]
+enum Type {
+    Known(NumberType),
+    Var(usize),
+}
+
+/// Unification over type variables where two types meet at the smallest type holding
+/// both, `Z` and `R` meet at `R`
+#[derive(Debug, Default)]
+struct Inference {
+    /// Union-find parent of each variable
+    parents: Vec<usize>,
+    /// Smallest type the variables of each class have to hold, `None` until something
+    /// constrains them
+    bounds: Vec<Option<NumberType>>,
+    /// Parameter and return types of each function in scope
+    functions: HashMap<String, (Vec<Type>, Type)>,
+    /// Type of each variable assigned at the top of the file
+    variables: HashMap<String, Type>,
+}
+
+/// Gives the parameters and return types of functions declared without one the type
+/// their bodies and the calls to them imply, checked as a whole file rather than one
+/// function at a time. A type nothing decides is the configured default one.
+/// `imported` are the declarations of the files the AST uses
+pub fn infer<'a>(
+    ast: &[AstNode],
+    imported: impl Iterator<Item = &'a AstNode>,
+    config: &Config,
+) -> Vec<AstNode> {
+    let mut inference = Inference::default();
+    let mut signatures = vec![];
+
+    for node in ast {
+        let AstNode::FunctionDeclaration(name, args, return_type, .., spans) = node else {
+            signatures.push(None);
+            continue;
+        };
+
+        let params = args
+            .iter()
+            .zip(&spans.params)
+            .map(|((_, r#type), (_, annotation))| match annotation {
+                Some(_) => Type::Known(*r#type),
+                None => inference.var(),
+            })
+            .collect::<Vec<Type>>();
+
+        let r#return = match spans.annotation {
+            Some(_) => Type::Known(*return_type),
+            None => inference.var(),
+        };
+
+        // Calls go to the first declaration, as they do in `Symbols`
+        inference
+            .functions
+            .entry(name.clone())
+            .or_insert((params.clone(), r#return));
+
+        signatures.push(Some((params, r#return)));
+    }
+
+    for node in imported {
+        if let AstNode::FunctionDeclaration(name, args, return_type, ..) = node {
+            inference.functions.entry(name.clone()).or_insert((
+                args.iter()
+                    .map(|(_, r#type)| Type::Known(*r#type))
+                    .collect(),
+                Type::Known(*return_type),
+            ));
+        }
+    }
+
+    let scope = HashMap::new();
+
+    for node in ast {
+        match node {
+            AstNode::Assignment((name, datatype), expr, _, spans) => {
+                let r#type = inference.expression(expr, &scope, config);
+
+                if spans.annotation.is_some()
+                    && let Some(datatype) = datatype
+                {
+                    inference.unify(r#type, Type::Known(*datatype));
+                }
+
+                let r#type = datatype.map_or(r#type, Type::Known);
+
+                inference.variables.insert(name.clone(), r#type);
+            }
+            AstNode::FunctionCall(name, args, _) => {
+                inference.call(name, args, &scope, config);
+            }
+            _ => {}
+        }
+    }
+
+    for (node, signature) in ast.iter().zip(&signatures) {
+        if let (AstNode::FunctionDeclaration(_, args, _, body, ..), Some((params, r#return))) =
+            (node, signature)
+        {
+            let scope = args
+                .iter()
+                .map(|(arg, _)| arg.clone())
+                .zip(params.iter().copied())
+                .collect::<HashMap<String, Type>>();
+
+            let body = inference.expression(body, &scope, config);
+
+            inference.unify(*r#return, body);
+        }
+    }
+
+    ast.iter()
+        .zip(signatures)
+        .map(|(node, signature)| {
+            let mut node = node.clone();
+
+            if let (
+                AstNode::FunctionDeclaration(_, args, return_type, ..),
+                Some((params, r#return)),
+            ) = (&mut node, signature)
+            {
+                for ((_, r#type), param) in args.iter_mut().zip(params) {
+                    *r#type = inference.resolve(param, config.default_type);
+                }
+
+                *return_type = inference.resolve(r#return, config.default_type);
+            }
+
+            node
+        })
+        .collect()
+}
+
+impl Inference {
+    fn var(&mut self) -> Type {
+        let var = self.parents.len();
+
+        self.parents.push(var);
+        self.bounds.push(None);
+
+        Type::Var(var)
+    }
+
+    fn find(&mut self, mut var: usize) -> usize {
+        while self.parents[var] != var {
+            self.parents[var] = self.parents[self.parents[var]];
+            var = self.parents[var];
+        }
+
+        var
+    }
+
+    /// Makes `a` and `b` the same type, returning it
+    fn unify(&mut self, a: Type, b: Type) -> Type {
+        match (a, b) {
+            (Type::Var(a), Type::Var(b)) => {
+                let a = self.find(a);
+                let b = self.find(b);
+
+                if a != b {
+                    self.parents[b] = a;
+                    self.bounds[a] = match (self.bounds[a], self.bounds[b]) {
+                        (Some(a), Some(b)) => Some(join(a, b)),
+                        (a, b) => a.or(b),
+                    };
+                }
+
+                Type::Var(a)
+            }
+            (Type::Var(var), Type::Known(r#type)) | (Type::Known(r#type), Type::Var(var)) => {
+                let var = self.find(var);
+
+                self.bounds[var] =
+                    Some(self.bounds[var].map_or(r#type, |bound| join(bound, r#type)));
+
+                Type::Var(var)
+            }
+            (Type::Known(a), Type::Known(b)) => Type::Known(join(a, b)),
+        }
+    }
+
+    /// The type settled for `r#type`, `fallback` when nothing decided it or the
+    /// constraints on it contradict each other
+    fn resolve(&mut self, r#type: Type, fallback: NumberType) -> NumberType {
+        let r#type = match r#type {
+            Type::Known(r#type) => Some(r#type),
+            Type::Var(var) => {
+                let var = self.find(var);
+
+                self.bounds[var]
+            }
+        };
+
+        match r#type {
+            Some(NumberType::Unknown) | None => fallback,
+            Some(r#type) => r#type,
+        }
+    }
+
+    fn expression(
+        &mut self,
+        expr: &Expression,
+        scope: &HashMap<String, Type>,
+        config: &Config,
+    ) -> Type {
+        match expr {
+            Expression::Abs(expr, _) => match self.expression(expr, scope, config) {
+                Type::Known(NumberType::Complex) => Type::Known(NumberType::Real),
+                r#type => r#type,
+            },
+            Expression::Binary(lhs, Token::Belongs, ..) => {
+                self.expression(lhs, scope, config);
+
+                Type::Known(NumberType::Bool)
+            }
+            Expression::Binary(lhs, op, rhs, _) => {
+                let lhs = self.expression(lhs, scope, config);
+                let rhs = self.expression(rhs, scope, config);
+
+                match (lhs, rhs) {
+                    _ if op.is_comparison() => {
+                        self.unify(lhs, rhs);
+
+                        Type::Known(NumberType::Bool)
+                    }
+                    // A scalar scales a matrix, it doesn't become one
+                    (Type::Known(NumberType::Matrix), _) | (_, Type::Known(NumberType::Matrix)) => {
+                        Type::Known(NumberType::Matrix)
+                    }
+                    // Arithmetic isn't defined on truth values, the parser reports it
+                    (Type::Known(NumberType::Bool), _) | (_, Type::Known(NumberType::Bool)) => {
+                        self.var()
+                    }
+                    _ => self.unify(lhs, rhs),
+                }
+            }
+            Expression::Branched(condition, then, otherwise, _) => {
+                let condition = self.expression(condition, scope, config);
+
+                self.unify(condition, Type::Known(NumberType::Bool));
+
+                let then = self.expression(then, scope, config);
+                let otherwise = self.expression(otherwise, scope, config);
+
+                self.unify(then, otherwise)
+            }
+            Expression::Identifier(name, _) => {
+                if let Some(r#type) = scope.get(name).or_else(|| self.variables.get(name)) {
+                    return *r#type;
+                }
+
+                match config
+                    .constants
+                    .get(name)
+                    .cloned()
+                    .or_else(|| evaluator::constant(name))
+                {
+                    Some(value) => Type::Known(value.datatype()),
+                    None => self.var(),
+                }
+            }
+            Expression::Integer(..) => Type::Known(NumberType::Int),
+            Expression::Real(..) => Type::Known(NumberType::Real),
+            Expression::Imaginary(..) => Type::Known(NumberType::Complex),
+            Expression::Matrix(rows, _) => {
+                for expr in rows.iter().flatten() {
+                    self.expression(expr, scope, config);
+                }
+
+                Type::Known(NumberType::Matrix)
+            }
+            Expression::FunctionCall(name, args, _) => self.call(name, args, scope, config),
+            Expression::Error(_) => self.var(),
+        }
+    }
+
+    /// Makes the arguments of a call the types of the parameters, returning the type
+    /// the call produces
+    fn call(
+        &mut self,
+        name: &str,
+        args: &[Expression],
+        scope: &HashMap<String, Type>,
+        config: &Config,
+    ) -> Type {
+        let args = args
+            .iter()
+            .map(|arg| self.expression(arg, scope, config))
+            .collect::<Vec<Type>>();
+
+        if let Some((params, r#return)) = self.functions.get(name).cloned() {
+            for (arg, param) in args.into_iter().zip(params) {
+                self.unify(param, arg);
+            }
+
+            return r#return;
+        }
+
+        if !STD.contains(&name) {
+            return self.var();
+        }
+
+        let (params, r#return) = internal_type_map(name);
+
+        // Only parameters taking a single type say anything about the argument
+        for (arg, accepted) in args.into_iter().zip(params) {
+            if let [r#type] = accepted[..] {
+                self.unify(arg, Type::Known(r#type));
+            }
+        }
+
+        match r#return {
+            NumberType::Unknown => self.var(),
+            r#type => Type::Known(r#type),
+        }
+    }
+}
+
+/// The smallest type holding both, `Unknown` for types that don't mix
+fn join(a: NumberType, b: NumberType) -> NumberType {
+    if a == b {
+        return a;
+    }
+
+    match (a.base(), b.base()) {
+        (NumberType::Unknown | NumberType::Bool, _)
+        | (_, NumberType::Unknown | NumberType::Bool) => NumberType::Unknown,
+        (NumberType::Matrix, _) | (_, NumberType::Matrix) => NumberType::Matrix,
+        (NumberType::Complex, _) | (_, NumberType::Complex) => NumberType::Complex,
+        (NumberType::Int, NumberType::Int) => NumberType::Int,
+        _ => NumberType::Real,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, syntax::SyntaxNode};
+
+    /// Parameter and return types of each function of `source` once inferred
+    fn signatures(source: &str) -> Vec<(String, Vec<NumberType>, NumberType)> {
+        let ast = Parser::new(&SyntaxNode::parse(source)).ast().unwrap();
+
+        infer(&ast, std::iter::empty(), &Config::default())
+            .into_iter()
+            .filter_map(|node| match node {
+                AstNode::FunctionDeclaration(name, args, return_type, ..) => Some((
+                    name,
+                    args.into_iter().map(|(_, r#type)| r#type).collect(),
+                    return_type,
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unannotated_return_is_inferred() {
+        assert_eq!(
+            signatures("fn one() = 1\nfn m(x) = [1, 2]\nfn b(x) = x > 1"),
+            [
+                ("one".to_string(), vec![], NumberType::Int),
+                ("m".to_string(), vec![NumberType::Real], NumberType::Matrix),
+                ("b".to_string(), vec![NumberType::Int], NumberType::Bool),
+            ]
+        );
+    }
+}
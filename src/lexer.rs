@@ -1,4 +1,4 @@
-use crate::token::{Token, TokenInfo};
+use crate::{syntax::{GreenToken, SyntaxKind}, token::{Token, TokenInfo}};
 use std::ops::RangeInclusive;
 
 pub struct Lexer<'a> {
@@ -10,30 +10,113 @@ impl<'a> Lexer<'a> {
 		Self { contents }
 	}
 
-	pub fn tokens(&self) -> Vec<Vec<TokenInfo>> {
+	/// Tokenizes the whole file into a single stream, every line is terminated by a
+	/// `Newline` token so the parser can decide where statements end
+	pub fn tokens(&self) -> Vec<TokenInfo> {
 		let mut offset = 1;
+		let mut tokens = vec![];
+		let mut block = false;
+
+		for line in self.contents.lines() {
+			let trimmed = line.trim_start();
+
+			if !block && trimmed.starts_with("///") && !trimmed.starts_with("////") {
+				let comment = &trimmed[3..];
+
+				tokens.push(TokenInfo::new(
+					Token::DocComment(comment.strip_prefix(' ').unwrap_or(comment).to_string()),
+					range_from_size(
+						offset + line.chars().count() - trimmed.chars().count(),
+						trimmed.chars().count(),
+					),
+				));
+			} else {
+				let code = strip_comments(line, &mut block);
+
+				if !code.trim().is_empty() {
+					tokens.extend(self.tokenize_line(&code, offset));
+				}
+			}
+
+			offset += line.chars().count();
+
+			tokens.push(TokenInfo::new(Token::Newline, range_from_size(offset, 1)));
 
-		self
-			.contents
+			offset += 1;
+		}
+
+		tokens
+	}
+
+	/// Whether a block comment is still open at the end of each line
+	pub fn open_comments(&self) -> Vec<bool> {
+		let mut block = false;
+
+		self.contents
 			.lines()
 			.map(|line| {
-				if !line.starts_with("//") && !line.is_empty() {
-					let tokens = self.tokenize_line(line, offset);
+				let trimmed = line.trim_start();
 
-					if let Some(token) = tokens.last() {
-						offset += *token.range.end();
-					}
-
-					tokens
-				} else {
-					offset += 1;
-					vec![]
+				if block || !trimmed.starts_with("///") || trimmed.starts_with("////") {
+					strip_comments(line, &mut block);
 				}
+
+				block
 			})
-			.filter(|x| !x.is_empty())
 			.collect()
 	}
 
+	/// Every piece of the source as a token, including the whitespace, comments and line
+	/// terminators `tokens` skips, but not the `*` it implies in `2x`. Their texts joined
+	/// give back the source
+	pub fn lossless_tokens(&self) -> Vec<GreenToken> {
+		let tokens = self.tokens();
+		let mut tokens = tokens.iter().filter(|t| t.range.start() != t.range.end()).peekable();
+		let mut lossless = vec![];
+		let mut offset = 1;
+		let mut block = false;
+
+		for line in self.contents.split_inclusive('\n') {
+			// The same terminators `lines` strips
+			let (text, terminator) = match line.strip_suffix('\n') {
+				Some(text) => match text.strip_suffix('\r') {
+					Some(text) => (text, "\r\n"),
+					None => (text, "\n"),
+				},
+				None => (line, ""),
+			};
+
+			let chars = text.chars().collect::<Vec<char>>();
+			let mut column = 0;
+
+			while let Some(tokeninfo) = tokens.next_if(|t| t.token != Token::Newline) {
+				let start = tokeninfo.range.start() - offset;
+				let end = tokeninfo.range.end() - offset;
+
+				trivia(&chars[column..start], &mut block, &mut lossless);
+
+				lossless.push(GreenToken::new(
+					SyntaxKind::from(&tokeninfo.token),
+					chars[start..end].iter().collect(),
+				));
+
+				column = end;
+			}
+
+			trivia(&chars[column..], &mut block, &mut lossless);
+
+			tokens.next();
+
+			if !terminator.is_empty() {
+				lossless.push(GreenToken::new(SyntaxKind::Newline, terminator.to_string()));
+			}
+
+			offset += chars.len() + 1;
+		}
+
+		lossless
+	}
+
 	fn tokenize_line(&self, line: &str, mut c: usize) -> Vec<TokenInfo> {
 		let mut line = line.chars().peekable();
 		let mut tokens = vec![];
@@ -46,7 +129,7 @@ impl<'a> Lexer<'a> {
 				if !token.is_empty() {
 					tokens.push(TokenInfo::new(
 						Token::new(token.clone()),
-						range_from_size(c, token.len()),
+						range_from_size(c, token.chars().count()),
 					));
 				}
 				break;
@@ -59,12 +142,30 @@ impl<'a> Lexer<'a> {
 				continue;
 			}
 
-			if char.is_ascii_alphabetic() {
+			if char == '"' {
+				token.push(char);
+
+				// Up to the closing quote, an unterminated string runs to the end of the line
+				for char in line.by_ref() {
+					token.push(char);
+
+					if char == '"' {
+						break;
+					}
+				}
+
+				tokens.push(TokenInfo::new(
+					Token::new(token.clone()),
+					range_from_size(c, token.chars().count()),
+				));
+				c += token.chars().count();
+				token.clear();
+			} else if is_identifier_start(char) {
 				token.push(char);
 				loop {
 					let char = line.peek();
 
-					if char.is_none() || !char.unwrap().is_ascii_alphabetic() {
+					if char.is_none() || !is_identifier_continue(*char.unwrap()) {
 						break;
 					}
 
@@ -74,37 +175,73 @@ impl<'a> Lexer<'a> {
 				}
 				tokens.push(TokenInfo::new(
 					Token::new(token.clone()),
-					range_from_size(c, token.len()),
+					range_from_size(c, token.chars().count()),
 				));
-				c += token.len();
+				c += token.chars().count();
 				token.clear();
 			} else if char.is_ascii_digit() {
 				token.push(char);
-				loop {
-					let char = line.peek();
 
-					if char.is_none() || (!char.unwrap().is_ascii_digit() && *char.unwrap() != '.') {
+				let radix = match (char, line.peek().copied()) {
+					('0', Some('x' | 'X')) if line.clone().nth(1).is_some_and(|c| c.is_ascii_hexdigit()) => 16,
+					('0', Some('b' | 'B')) if line.clone().nth(1).is_some_and(|c| c == '0' || c == '1') => 2,
+					_ => 10,
+				};
+
+				if radix != 10 {
+					token.push(line.next().unwrap());
+				}
+
+				while let Some(&char) = line.peek() {
+					if radix == 10 && matches!(char, 'e' | 'E') {
+						// Only an exponent when digits follow, `2e` is still `2 * e`
+						let mut rest = line.clone().skip(1);
+
+						let exponent = match rest.next() {
+							Some('+' | '-') => rest.next().is_some_and(|c| c.is_ascii_digit()),
+							Some(c) => c.is_ascii_digit(),
+							None => false,
+						};
+
+						if !exponent {
+							break;
+						}
+
+						token.push(line.next().unwrap());
+
+						if let Some('+' | '-') = line.peek() {
+							token.push(line.next().unwrap());
+						}
+
+						continue;
+					}
+
+					if !char.is_digit(radix) && char != '_' && (radix != 10 || char != '.') {
 						break;
 					}
 
-					let char = line.next();
+					token.push(line.next().unwrap());
+				}
 
-					token.push(char.unwrap());
+				// A trailing `i` makes the number imaginary, as long as it doesn't start an identifier
+				if line.peek() == Some(&'i') && !line.clone().nth(1).is_some_and(is_identifier_continue) {
+					token.push(line.next().unwrap());
 				}
+
 				tokens.push(TokenInfo::new(
 					Token::new(token.clone()),
-					range_from_size(c, token.len()),
+					range_from_size(c, token.chars().count()),
 				));
-				c += token.len();
+				c += token.chars().count();
 				token.clear();
 			} else {
 				token.push(char);
-				let punctuation = ['.', '(', ')', '{', '}', '[', ']', '|', ','];
+				let punctuation = ['.', '(', ')', '{', '}', '[', ']', '|', ',', '"'];
 				loop {
 					let char = line.peek();
 
 					if char.is_none()
-						|| char.unwrap().is_ascii_alphanumeric()
+						|| is_identifier_continue(*char.unwrap())
 						|| char.unwrap().is_whitespace()
 						|| punctuation.contains(char.unwrap())
 						|| punctuation.map(|f| token.contains(f)).contains(&true)
@@ -118,38 +255,31 @@ impl<'a> Lexer<'a> {
 				}
 				tokens.push(TokenInfo::new(
 					Token::new(token.clone()),
-					range_from_size(c, token.len()),
+					range_from_size(c, token.chars().count()),
 				));
-				c += token.len();
+				c += token.chars().count();
 				token.clear();
 			}
 		}
 
 		let mut r = vec![];
 
-		let mut offset = 0;
-
 		for i in 0..tokens.len() {
 			let tokeninfo = tokens.get(i).unwrap();
 
-			r.push(TokenInfo::new(
-				tokeninfo.token.clone(),
-				*tokeninfo.range.start()..=tokeninfo.range.end() + offset,
-			));
+			r.push(tokeninfo.clone());
 
 			if tokens.get(i + 1).is_none() {
 				break;
 			}
 
-			if let Token::Integer(_) = tokeninfo.token {
-				if let Token::Identifier(_) = tokens[i + 1].token {
-					offset += 1;
+			// `2x` is `2 * x`, the implied `*` takes no space in the source
+			if let Token::Integer(_) | Token::Float(_) = tokeninfo.token
+				&& let Token::Identifier(_) | Token::Imaginary(_) = tokens[i + 1].token
+			{
+				let end = *tokeninfo.range.end();
 
-					r.push(TokenInfo::new(
-						Token::Mul,
-						*tokeninfo.range.start()..=tokeninfo.range.end() + offset,
-					));
-				}
+				r.push(TokenInfo::new(Token::Mul, end..=end));
 			}
 		}
 
@@ -157,6 +287,82 @@ impl<'a> Lexer<'a> {
 	}
 }
 
+/// Blanks out `//` and `/* */` comments with spaces so offsets of the remaining code are
+/// preserved, `block` carries an unterminated block comment over to the next line. Strings
+/// are kept as they are, `"lib//a.cal"` holds no comment
+fn strip_comments(line: &str, block: &mut bool) -> String {
+	let mut code = String::new();
+	let mut chars = line.chars().peekable();
+	let mut string = false;
+
+	while let Some(char) = chars.next() {
+		if string {
+			string = char != '"';
+			code.push(char);
+		} else if *block {
+			if char == '*' && chars.peek() == Some(&'/') {
+				chars.next();
+				code.push(' ');
+				*block = false;
+			}
+
+			code.push(' ');
+		} else if char == '/' && chars.peek() == Some(&'/') {
+			break;
+		} else if char == '/' && chars.peek() == Some(&'*') {
+			chars.next();
+			code.push_str("  ");
+			*block = true;
+		} else {
+			string = char == '"';
+			code.push(char);
+		}
+	}
+
+	code
+}
+
+/// Splits the source between two tokens into whitespace and comments, tracking block
+/// comments the same way `strip_comments` does
+fn trivia(gap: &[char], block: &mut bool, lossless: &mut Vec<GreenToken>) {
+	let mut rest = gap;
+
+	while !rest.is_empty() {
+		let (kind, len) = if *block || rest.starts_with(&['/', '*']) {
+			let skip = if *block { 0 } else { 2 };
+
+			*block = true;
+
+			match rest[skip..].windows(2).position(|w| w == ['*', '/']) {
+				Some(end) => {
+					*block = false;
+					(SyntaxKind::Comment, skip + end + 2)
+				}
+				None => (SyntaxKind::Comment, rest.len()),
+			}
+		} else if rest.starts_with(&['/', '/']) {
+			(SyntaxKind::Comment, rest.len())
+		} else if rest[0].is_whitespace() {
+			(SyntaxKind::Whitespace, rest.iter().take_while(|c| c.is_whitespace()).count())
+		} else {
+			(SyntaxKind::LexError, 1)
+		};
+
+		lossless.push(GreenToken::new(kind, rest[..len].iter().collect()));
+		rest = &rest[len..];
+	}
+}
+
+/// Identifiers start with `_` or an XID_Start character, which includes Greek letters
+/// such as `π`, and continue with XID_Continue characters
+pub fn is_identifier_start(char: char) -> bool {
+	char == '_' || unicode_ident::is_xid_start(char)
+}
+
+pub fn is_identifier_continue(char: char) -> bool {
+	unicode_ident::is_xid_continue(char)
+}
+
 fn range_from_size(start: usize, size: usize) -> RangeInclusive<usize> {
 	start..=start + size
 }
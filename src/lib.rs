@@ -0,0 +1,20 @@
+pub mod ast;
+pub mod completion;
+pub mod config;
+pub mod constant;
+pub mod database;
+pub mod docs;
+pub mod document;
+pub mod evaluator;
+pub mod format;
+pub mod inference;
+pub mod lexer;
+pub mod number;
+pub mod parser;
+pub mod position;
+pub mod ranges;
+pub mod recursion;
+pub mod standardlibrary;
+pub mod syntax;
+pub mod token;
+pub mod types;
@@ -1,50 +1,99 @@
-mod ast;
-mod lexer;
-mod parser;
-mod standardlibrary;
-mod token;
-mod types;
-
-use std::f32::consts::{E, PI};
-
-use ast::AstNode;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use calcagebra_ls::ast::{AstNode, Expression, Span};
+use calcagebra_ls::completion::{
+    Context, TEMPLATES, call_snippet, enclosing_call, keyword_snippet, rank,
+};
+use calcagebra_ls::config::{CONFIG_FILE, Config};
+use calcagebra_ls::database::{Cancellable, Database, Snapshot, name};
+use calcagebra_ls::docs::{
+    Docs, detail, fence, signature, std_detail, std_params, std_signature, token_docs, type_docs,
+};
+use calcagebra_ls::evaluator::EvalError;
+use calcagebra_ls::format::reformat;
+use calcagebra_ls::lexer::is_identifier_start;
+use calcagebra_ls::parser::Parser;
+use calcagebra_ls::position::{LineIndex, token_at};
+use calcagebra_ls::ranges::{enclosing, folding_ranges, selection_range};
+use calcagebra_ls::recursion::Recursion;
+use calcagebra_ls::standardlibrary::{STD, internal_type_map};
+use calcagebra_ls::syntax::SyntaxNode;
+use calcagebra_ls::token::Token;
+use calcagebra_ls::types::NumberType;
 use dashmap::DashMap;
-
-use lexer::Lexer;
-use parser::Parser;
-use serde_json::Value;
+use serde_json::{Value, json};
 use simsearch::{SearchOptions, SimSearch};
-use token::Token;
-use tower_lsp::jsonrpc::Result;
+use tower_lsp::jsonrpc::{self, Result};
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
 
-use crate::standardlibrary::{STD, internal_type_map};
-use crate::types::NumberType;
+/// Upper bound on the time spent evaluating a worksheet for inlay hints
+const EVALUATION_TIMEOUT: Duration = Duration::from_millis(500);
 
 #[derive(Debug, Clone)]
 struct Backend {
     client: Client,
-    file: DashMap<usize, String>,
+    /// The open documents and the analyses memoized on them
+    db: Arc<Database>,
+    /// Whether hover explains operators, keywords and type names, set by the client's
+    /// `token_hover` initialization option
+    token_hover: Arc<AtomicBool>,
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, param: InitializeParams) -> Result<InitializeResult> {
+        let token_hover = param
+            .initialization_options
+            .as_ref()
+            .and_then(|options| options.get("token_hover"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+
+        self.token_hover.store(token_hover, Ordering::Relaxed);
+
+        // `use` paths are relative to the workspace folders
+        #[allow(deprecated)]
+        let roots = match (param.workspace_folders, param.root_uri) {
+            (Some(folders), _) => folders.into_iter().map(|folder| folder.uri).collect(),
+            (None, Some(root)) => vec![root],
+            (None, None) => vec![],
+        };
+
+        self.db.set_roots(
+            roots
+                .iter()
+                .filter_map(|root| root.to_file_path().ok())
+                .collect(),
+        );
+
         Ok(InitializeResult {
             server_info: None,
             capabilities: ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                    TextDocumentSyncKind::INCREMENTAL,
                 )),
                 completion_provider: Some(CompletionOptions {
-                    resolve_provider: Some(false),
+                    resolve_provider: Some(true),
                     trigger_characters: Some(vec![".".to_string()]),
                     work_done_progress_options: Default::default(),
                     all_commit_characters: None,
                     ..Default::default()
                 }),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
+                definition_provider: Some(OneOf::Left(true)),
+                call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec!["(".to_string(), ",".to_string()]),
+                    retrigger_characters: None,
+                    work_done_progress_options: Default::default(),
+                }),
+                inlay_hint_provider: Some(OneOf::Left(true)),
                 execute_command_provider: Some(ExecuteCommandOptions {
                     commands: vec![],
                     work_done_progress_options: Default::default(),
@@ -70,13 +119,54 @@ impl LanguageServer for Backend {
         self.client
             .log_message(MessageType::INFO, "calcagebra-ls initialized")
             .await;
+
+        self.configure().await;
+
+        // Imported files that aren't open are read from disk, they are read again when
+        // they change there, as is the configuration
+        let watchers = DidChangeWatchedFilesRegistrationOptions {
+            watchers: ["**/*.cal".to_string(), format!("**/{CONFIG_FILE}")]
+                .into_iter()
+                .map(|pattern| FileSystemWatcher {
+                    glob_pattern: GlobPattern::String(pattern),
+                    kind: None,
+                })
+                .collect(),
+        };
+
+        let registration = Registration {
+            id: "calcagebra-files".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(watchers).ok(),
+        };
+
+        if let Err(err) = self.client.register_capability(vec![registration]).await {
+            self.client.log_message(MessageType::WARNING, err).await;
+        }
     }
 
     async fn shutdown(&self) -> Result<()> {
         Ok(())
     }
 
-    async fn did_change_workspace_folders(&self, _: DidChangeWorkspaceFoldersParams) {
+    async fn did_change_workspace_folders(&self, param: DidChangeWorkspaceFoldersParams) {
+        let path = |folder: WorkspaceFolder| folder.uri.to_file_path().ok();
+
+        let removed = param
+            .event
+            .removed
+            .into_iter()
+            .filter_map(path)
+            .collect::<Vec<_>>();
+
+        let mut roots = self.db.roots();
+
+        roots.retain(|root| !removed.contains(root));
+        roots.extend(param.event.added.into_iter().filter_map(path));
+
+        self.db.set_roots(roots);
+        self.configure().await;
+
         self.client
             .log_message(MessageType::INFO, "workspace folders changed!")
             .await;
@@ -88,7 +178,21 @@ impl LanguageServer for Backend {
             .await;
     }
 
-    async fn did_change_watched_files(&self, _: DidChangeWatchedFilesParams) {
+    async fn did_change_watched_files(&self, param: DidChangeWatchedFilesParams) {
+        let mut configured = false;
+
+        for change in param.changes {
+            if change.uri.path().ends_with(&format!("/{CONFIG_FILE}")) {
+                configured = true;
+            } else {
+                self.db.changed_on_disk(&change.uri);
+            }
+        }
+
+        if configured {
+            self.configure().await;
+        }
+
         self.client
             .log_message(MessageType::INFO, "watched files have changed!")
             .await;
@@ -109,17 +213,11 @@ impl LanguageServer for Backend {
     }
 
     async fn did_open(&self, param: DidOpenTextDocumentParams) {
-        self.file.clear();
-        let lines = param
-            .text_document
-            .text
-            .lines()
-            .map(|f| f.to_string())
-            .collect::<Vec<String>>();
-
-        for (i, line) in lines.iter().enumerate() {
-            self.file.insert(i, line.to_string());
-        }
+        self.db.open(
+            param.text_document.uri,
+            param.text_document.text,
+            param.text_document.version,
+        );
 
         self.client
             .log_message(MessageType::INFO, "file opened!")
@@ -127,16 +225,12 @@ impl LanguageServer for Backend {
     }
 
     async fn did_change(&self, param: DidChangeTextDocumentParams) {
-        self.file.clear();
-        let lines = param.content_changes[0]
-            .text
-            .lines()
-            .map(|f| f.to_string())
-            .collect::<Vec<String>>();
-
-        for (i, line) in lines.iter().enumerate() {
-            self.file.insert(i, line.to_string());
-        }
+        // Requests still running on the previous text are cancelled
+        self.db.edit(
+            &param.text_document.uri,
+            &param.content_changes,
+            param.text_document.version,
+        );
 
         self.client
             .log_message(MessageType::INFO, "file changed!")
@@ -149,7 +243,9 @@ impl LanguageServer for Backend {
             .await;
     }
 
-    async fn did_close(&self, _: DidCloseTextDocumentParams) {
+    async fn did_close(&self, param: DidCloseTextDocumentParams) {
+        self.db.close(&param.text_document.uri);
+
         self.client
             .log_message(MessageType::INFO, "file closed!")
             .await;
@@ -157,87 +253,18 @@ impl LanguageServer for Backend {
 
     async fn diagnostic(
         &self,
-        _: DocumentDiagnosticParams,
+        param: DocumentDiagnosticParams,
     ) -> Result<DocumentDiagnosticReportResult> {
-        let mut file = self
-            .file
-            .iter()
-            .map(|f| (*f.key(), f.to_string()))
-            .collect::<Vec<(usize, String)>>();
-
-        file.sort_by_key(|k| k.0);
-
-        let file = &file
-            .iter()
-            .map(|(_, v)| v.to_string())
-            .collect::<Vec<String>>()
-            .join("\n");
-
-        let char_per_line = &file.lines().map(|f| f.len() + 1).collect::<Vec<usize>>();
+        let uri = param.text_document.uri;
 
-        let mut items = vec![];
-
-        Parser::new(Lexer::new(file).tokens())
-            .ast()
-            .unwrap_or_default()
-            .iter()
-            .filter(|f| matches!(f, AstNode::Error(..)))
-            .for_each(|f| {
-                if let AstNode::Error(message, range) = f {
-                    let (start, end) = range.clone().into_inner();
-
-                    let mut start_line = 0;
-                    let mut start_sum = 0;
-
-                    let mut end_line = 0;
-                    let mut end_sum = 0;
-
-                    for chars in char_per_line {
-                        start_sum += chars;
-                        if start <= start_sum + 1 {
-                            break;
-                        }
-                        start_line += 1;
-                    }
-
-                    for chars in char_per_line {
-                        if end <= end_sum + 1 {
-                            break;
-                        }
-                        end_sum += chars;
-                        end_line += 1;
-                    }
-
-                    items.push(Diagnostic {
-                        range: Range::new(
-                            Position::new(
-                                start_line,
-                                // (start_sum as isize - start as isize)
-                                //     .abs()
-                                //     .try_into()
-                                //     .unwrap(),
-                                0,
-                            ),
-                            Position::new(
-                                end_line,
-                                // (end_sum as isize - end as isize).abs().try_into().unwrap(),
-                                0,
-                            ),
-                        ),
-                        severity: Some(DiagnosticSeverity::ERROR),
-                        message: message.to_string(),
-                        source: Some("calcagebra".to_string()),
-                        ..Default::default()
-                    })
-                }
-            });
+        let items = self.query(move |db| db.diagnostics(&uri)).await?;
 
         Ok(DocumentDiagnosticReportResult::Report(
             DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
                 related_documents: None,
                 full_document_diagnostic_report: FullDocumentDiagnosticReport {
                     result_id: None,
-                    items,
+                    items: items.to_vec(),
                 },
             }),
         ))
@@ -248,12 +275,60 @@ impl LanguageServer for Backend {
             .log_message(MessageType::INFO, "completion requested!")
             .await;
 
-        let file = &self
-            .file
-            .iter()
-            .map(|f| f.to_string())
-            .collect::<Vec<String>>()
-            .join("\n");
+        let uri = param.text_document_position.text_document.uri;
+        let position = param.text_document_position.position;
+
+        let (document, symbols, declared, config) = {
+            let uri = uri.clone();
+
+            self.query(move |db| {
+                Ok((
+                    db.document(&uri)?,
+                    db.symbols(&uri)?,
+                    db.types(&uri)?,
+                    db.config(&uri),
+                ))
+            })
+            .await?
+        };
+
+        let before = document.before(position);
+        let offset = LineIndex::new(document.text()).offset(position);
+
+        // The word being typed is the part of the identifier before the cursor
+        let start = document
+            .token_at(offset - 1)
+            .map_or(offset, |f| *f.range.start());
+
+        let text = before
+            .chars()
+            .skip(before.chars().count() - (offset - start))
+            .collect::<String>();
+
+        let text = if text.starts_with(is_identifier_start) {
+            text
+        } else {
+            String::new()
+        };
+
+        let context = Context::new(&before[..before.len() - text.len()]);
+
+        match context {
+            Context::TypeAnnotation => {
+                return Ok(Some(CompletionResponse::Array(
+                    NumberType::dictionary()
+                        .into_iter()
+                        .map(|f| CompletionItem {
+                            label: f,
+                            kind: Some(CompletionItemKind::TYPE_PARAMETER),
+                            ..Default::default()
+                        })
+                        .collect(),
+                )));
+            }
+            Context::Declaration => return Ok(Some(CompletionResponse::Array(vec![]))),
+            _ => {}
+        }
 
         let mut variables = ["pi", "π", "e"]
             .iter()
@@ -261,59 +336,96 @@ impl LanguageServer for Backend {
             .collect::<Vec<String>>();
 
         let mut functions = vec![];
-        let functions_docs = DashMap::new();
+        let functions_details = DashMap::new();
+        let functions_snippets = DashMap::new();
 
-        let tokens = Token::dictionary();
+        // Type of the value each name produces and the parameter types of each function,
+        // used to rank the candidates
+        let types = DashMap::new();
+        let parameters = DashMap::new();
 
-        Parser::new(Lexer::new(file).tokens())
-            .ast()
-            .unwrap_or_default()
-            .iter()
-            .filter(|f| {
-                matches!(
-                    f,
-                    AstNode::Assignment(..) | AstNode::FunctionDeclaration(..)
-                )
-            })
-            .for_each(|f| match f {
-                AstNode::Assignment((ident, _), _) => variables.push(ident.clone()),
-                AstNode::FunctionDeclaration(name, args, return_type, _) => {
-                    functions_docs.insert(
-                        name.to_string(),
-                        format!(
-                            "fn {name}({}): {return_type}",
-                            args.iter()
-                                .map(|(name, r#type)| format!("{name}: {type}"))
-                                .collect::<Vec<String>>()
-                                .join(",")
-                        ),
-                    );
-                    functions.push(name.to_string());
-                }
-                _ => {}
-            });
+        for constant in &variables {
+            types.insert(constant.to_string(), Some(NumberType::Real));
+        }
 
-        let id = param.text_document_position.position.line as usize;
-        let character = param.text_document_position.position.character as usize;
-        let line = match self.file.get(&id) {
-            Some(file) => file.to_string(),
-            None => String::new(),
-        };
+        for (constant, value) in &config.constants {
+            variables.push(constant.clone());
+            types.insert(constant.clone(), Some(value.datatype()));
+        }
 
-        let mut text = String::new();
+        for f in STD {
+            types.insert(f.to_string(), Some(internal_type_map(f).1));
+        }
 
-        for (i, char) in line.split("").enumerate() {
-            let c = char.chars().next();
-            if c.is_some() && !c.unwrap().is_ascii_alphanumeric() {
-                text = String::new();
-                continue;
-            }
-            text += char;
-            if i == character {
-                break;
-            }
+        for (template, _) in TEMPLATES {
+            types.insert(template.to_string(), Some(NumberType::Matrix));
         }
 
+        let keywords = Token::dictionary();
+
+        for (name, r#type) in &declared.values {
+            types.insert(name.clone(), *r#type);
+        }
+
+        for (name, args) in &declared.parameters {
+            parameters.insert(
+                name.clone(),
+                args.iter()
+                    .map(|r#type| vec![*r#type])
+                    .collect::<Vec<Vec<NumberType>>>(),
+            );
+        }
+
+        symbols.iter().for_each(|f| match f {
+            AstNode::Assignment((ident, _), ..) => variables.push(ident.clone()),
+            AstNode::FunctionDeclaration(name, args, return_type, ..) => {
+                functions_details.insert(name.to_string(), detail(args, return_type));
+                functions_snippets.insert(
+                    name.to_string(),
+                    call_snippet(
+                        name,
+                        &args
+                            .iter()
+                            .map(|(arg, _)| arg.to_string())
+                            .collect::<Vec<String>>(),
+                    ),
+                );
+                functions.push(name.to_string());
+            }
+            _ => {}
+        });
+
+        let expected = match &context {
+            Context::Argument(name, argument) => parameters
+                .get(name)
+                .map(|f| f.clone())
+                .or_else(|| {
+                    STD.contains(&name.as_str())
+                        .then(|| internal_type_map(name).0)
+                })
+                .and_then(|f| f.get(*argument).cloned()),
+            _ => None,
+        };
+
+        // Candidates that fit the context sort first, the editor still filters by prefix
+        let sort_text = |label: &str| {
+            let rank = match (&context, &expected) {
+                (Context::Argument(..), Some(expected)) => {
+                    rank(types.get(label).and_then(|f| *f), expected)
+                }
+                (Context::Condition, _) => {
+                    rank(types.get(label).and_then(|f| *f), &[NumberType::Bool])
+                }
+                _ => return None,
+            };
+
+            Some(format!("{rank}{label}"))
+        };
+
+        // Documentation is filled in by `completion_resolve`, the item only carries the
+        // name to look up and the document it is looked up in
+        let data = |label: &str| Some(json!({ "name": label, "uri": uri }));
+
         let mut responses = vec![];
 
         self.get_closest_match(&text, variables)
@@ -321,7 +433,15 @@ impl LanguageServer for Backend {
             .for_each(|f| {
                 responses.push(CompletionItem {
                     label: f.to_string(),
+                    label_details: Some(CompletionItemLabelDetails {
+                        detail: None,
+                        description: types
+                            .get(f)
+                            .map(|f| f.unwrap_or(NumberType::Unknown).to_string()),
+                    }),
                     kind: Some(CompletionItemKind::VARIABLE),
+                    data: data(f),
+                    sort_text: sort_text(f),
                     ..Default::default()
                 })
             });
@@ -331,19 +451,51 @@ impl LanguageServer for Backend {
             .for_each(|f| {
                 responses.push(CompletionItem {
                     label: f.to_string(),
-                    documentation: Some(Documentation::MarkupContent(MarkupContent {
-                        kind: MarkupKind::PlainText,
-                        value: functions_docs.get(f).unwrap().to_string(),
-                    })),
+                    label_details: Some(CompletionItemLabelDetails {
+                        detail: functions_details.get(f).map(|f| f.to_string()),
+                        description: None,
+                    }),
                     kind: Some(CompletionItemKind::FUNCTION),
+                    data: data(f),
+                    sort_text: sort_text(f),
+                    insert_text: functions_snippets.get(f).map(|f| f.to_string()),
+                    insert_text_format: Some(InsertTextFormat::SNIPPET),
                     ..Default::default()
                 })
             });
 
-        self.get_closest_match(&text, tokens).iter().for_each(|f| {
+        self.get_closest_match(&text, keywords)
+            .iter()
+            .for_each(|f| {
+                let snippet = keyword_snippet(f);
+
+                responses.push(CompletionItem {
+                    label: f.to_string(),
+                    kind: Some(CompletionItemKind::KEYWORD),
+                    sort_text: sort_text(f),
+                    insert_text: snippet.map(str::to_string),
+                    insert_text_format: snippet.map(|_| InsertTextFormat::SNIPPET),
+                    ..Default::default()
+                })
+            });
+
+        self.get_closest_match(
+            &text,
+            TEMPLATES.iter().map(|(f, _)| f.to_string()).collect(),
+        )
+        .iter()
+        .for_each(|f| {
+            let (_, snippet) = TEMPLATES
+                .iter()
+                .find(|(template, _)| template == f)
+                .unwrap();
+
             responses.push(CompletionItem {
                 label: f.to_string(),
-                kind: Some(CompletionItemKind::KEYWORD),
+                kind: Some(CompletionItemKind::SNIPPET),
+                sort_text: sort_text(f),
+                insert_text: Some(snippet.to_string()),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
                 ..Default::default()
             })
         });
@@ -354,28 +506,17 @@ impl LanguageServer for Backend {
         )
         .iter()
         .for_each(|f| {
-            let type_map = internal_type_map(f);
-
             responses.push(CompletionItem {
                 label: f.to_string(),
-                documentation: Some(Documentation::MarkupContent(MarkupContent {
-                    kind: MarkupKind::Markdown,
-                    value: format!(
-                        "fn {f}({}): {}",
-                        type_map
-                            .0
-                            .iter()
-                            .map(|g| g
-                                .iter()
-                                .map(|h| h.to_string())
-                                .collect::<Vec<String>>()
-                                .join("|"))
-                            .collect::<Vec<String>>()
-                            .join(","),
-                        type_map.1
-                    ),
-                })),
+                label_details: Some(CompletionItemLabelDetails {
+                    detail: Some(std_detail(f)),
+                    description: None,
+                }),
                 kind: Some(CompletionItemKind::FUNCTION),
+                data: data(f),
+                sort_text: sort_text(f),
+                insert_text: Some(call_snippet(f, &std_params(f))),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
                 ..Default::default()
             })
         });
@@ -383,117 +524,672 @@ impl LanguageServer for Backend {
         Ok(Some(CompletionResponse::Array(responses)))
     }
 
+    async fn completion_resolve(&self, mut item: CompletionItem) -> Result<CompletionItem> {
+        self.client
+            .log_message(MessageType::INFO, "completion resolve requested!")
+            .await;
+
+        let name = item
+            .data
+            .as_ref()
+            .and_then(|data| data.get("name"))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        let uri = item
+            .data
+            .as_ref()
+            .and_then(|data| data.get("uri"))
+            .and_then(|uri| serde_json::from_value::<Url>(uri.clone()).ok());
+
+        if let (Some(name), Some(uri)) = (name, uri) {
+            let documentation = self
+                .query(move |db| documentation(&db, &uri, &name))
+                .await?;
+
+            item.documentation = documentation.map(|value| {
+                Documentation::MarkupContent(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value,
+                })
+            });
+        }
+
+        Ok(item)
+    }
+
     async fn hover(&self, param: HoverParams) -> Result<Option<Hover>> {
         self.client
             .log_message(MessageType::INFO, "hover requested!")
             .await;
 
-        let file = &self
-            .file
-            .iter()
-            .map(|f| f.to_string())
-            .collect::<Vec<String>>()
-            .join("\n");
+        let uri = param.text_document_position_params.text_document.uri;
 
-        let mut variables = vec![
-            ("pi".to_string(), PI.to_string()),
-            ("π".to_string(), PI.to_string()),
-            ("e".to_string(), E.to_string()),
-        ];
+        let (document, tokens) = {
+            let uri = uri.clone();
 
-        let mut functions = vec![];
-        let functions_docs = DashMap::new();
+            self.query(move |db| Ok((db.document(&uri)?, db.tokens(&uri)?)))
+                .await?
+        };
 
-        let std_docs = STD.map(|f| f.to_string());
+        let index = LineIndex::new(document.text());
+        let token_hover = self.token_hover.load(Ordering::Relaxed);
+        let offset = index.offset(param.text_document_position_params.position);
 
-        Parser::new(Lexer::new(file).tokens())
-            .ast()
-            .unwrap_or_default()
-            .iter()
-            .filter(|f| {
-                matches!(
-                    f,
-                    AstNode::Assignment(..) | AstNode::FunctionDeclaration(..)
-                )
+        let Some(tokeninfo) = token_at(&tokens, offset) else {
+            return Ok(None);
+        };
+
+        let mut range = Some(Range::new(
+            index.position(*tokeninfo.range.start()),
+            index.position(*tokeninfo.range.end()),
+        ));
+
+        let response = match &tokeninfo.token {
+            Token::Integer(literal) | Token::Float(literal) => {
+                let r#type = if literal.is_real() {
+                    NumberType::Real
+                } else {
+                    NumberType::Int
+                };
+
+                return Ok(Some(Hover {
+                    contents: HoverContents::Array(vec![MarkedString::String(format!(
+                        "{literal}: {type} = {}",
+                        literal.exact()
+                    ))]),
+                    range,
+                }));
+            }
+            Token::Imaginary(literal) => {
+                return Ok(Some(Hover {
+                    contents: HoverContents::Array(vec![MarkedString::String(format!(
+                        "{literal}: {} = {}i",
+                        NumberType::Complex,
+                        literal.exact()
+                    ))]),
+                    range,
+                }));
+            }
+            Token::Identifier(name) => {
+                // Type names appear in annotations and as the set on the right of `E`
+                let annotation = tokens
+                    .iter()
+                    .rev()
+                    .find(|f| f.range.end() <= tokeninfo.range.start())
+                    .is_some_and(|f| matches!(f.token, Token::Colon | Token::Belongs));
+
+                let documentation = {
+                    let uri = uri.clone();
+                    let name = name.clone();
+
+                    self.query(move |db| documentation(&db, &uri, &name))
+                        .await?
+                };
+
+                match (documentation, NumberType::parse(name)) {
+                    (Some(docs), _) => docs,
+                    (None, Some(r#type)) if annotation && token_hover => type_docs(r#type),
+                    _ => String::new(),
+                }
+            }
+            token if token_hover => token_docs(token).unwrap_or_default(),
+            _ => String::new(),
+        };
+
+        // Inside an expression the smallest part of it holding the token is evaluated,
+        // names and literals already show their value
+        let evaluated = self
+            .query(move |db| {
+                let ast = db.ast(&uri)?;
+
+                let Some((statement, exprs)) = enclosing(&ast, offset) else {
+                    return Ok(None);
+                };
+
+                match exprs.last() {
+                    Some(
+                        Expression::Identifier(..)
+                        | Expression::Integer(..)
+                        | Expression::Real(..)
+                        | Expression::Imaginary(..)
+                        | Expression::Error(_),
+                    )
+                    | None => Ok(None),
+                    Some(expr) => Ok(db
+                        .evaluate(&uri, statement, expr)?
+                        .ok()
+                        .map(|value| (expr.span().clone(), value))),
+                }
             })
-            .for_each(|f| {
-                if let AstNode::Assignment((ident, datatype), _) = f {
-                    variables.push((
-                        ident.clone(),
-                        datatype.unwrap_or(NumberType::Unknown).to_string(),
-                    ))
+            .await?;
+
+        let response = match evaluated {
+            Some((span, value)) => {
+                let (start, end) = span.into_inner();
+
+                // Written on one line however the expression is broken up
+                let source = document
+                    .text()
+                    .chars()
+                    .skip(start - 1)
+                    .take(end - start)
+                    .collect::<String>()
+                    .split_whitespace()
+                    .collect::<Vec<&str>>()
+                    .join(" ");
+
+                range = Some(Range::new(index.position(start), index.position(end)));
+
+                let value = fence(&format!("{source}: {} = {value}", value.datatype()));
+
+                if response.is_empty() {
+                    value
+                } else {
+                    format!("{value}\n\n{response}")
+                }
+            }
+            None => response,
+        };
+
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: response,
+            }),
+            range,
+        }))
+    }
+
+    async fn goto_definition(
+        &self,
+        param: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        self.client
+            .log_message(MessageType::INFO, "definition requested!")
+            .await;
+
+        let uri = param.text_document_position_params.text_document.uri;
+        let position = param.text_document_position_params.position;
+
+        let location = self
+            .query(move |db| {
+                let document = db.document(&uri)?;
+                let tokens = db.tokens(&uri)?;
+                let offset = LineIndex::new(document.text()).offset(position);
+
+                let Some(tokeninfo) = token_at(&tokens, offset) else {
+                    return Ok(None);
+                };
+
+                let name = match &tokeninfo.token {
+                    // The path of a `use` leads to the file it imports
+                    Token::Str(path) => {
+                        return Ok(db
+                            .resolve(&uri, path)
+                            .map(|file| Location::new(file, Range::default())));
+                    }
+                    Token::Identifier(name) => name,
+                    _ => return Ok(None),
+                };
+
+                // Inside a function body its parameters hide the declarations around it
+                let param = db.ast(&uri)?.iter().find_map(|node| match node {
+                    AstNode::FunctionDeclaration(_, args, .., spans)
+                        if spans.node.contains(&offset) =>
+                    {
+                        args.iter()
+                            .position(|(arg, _)| arg == name)
+                            .map(|i| spans.params[i].0.clone())
+                    }
+                    _ => None,
+                });
+
+                let (file, span) = match (param, db.symbols(&uri)?.lookup(name)) {
+                    (Some(span), _) => (uri.clone(), span),
+                    (
+                        None,
+                        Some((
+                            file,
+                            AstNode::Assignment(.., spans)
+                            | AstNode::FunctionDeclaration(.., spans),
+                        )),
+                    ) => (file.unwrap_or(&uri).clone(), spans.name.clone()),
+                    _ => return Ok(None),
+                };
+
+                let target = db.document(&file)?;
+                let index = LineIndex::new(target.text());
+
+                Ok(Some(Location::new(
+                    file,
+                    Range::new(index.position(*span.start()), index.position(*span.end())),
+                )))
+            })
+            .await?;
+
+        Ok(location.map(GotoDefinitionResponse::Scalar))
+    }
+
+    async fn prepare_call_hierarchy(
+        &self,
+        param: CallHierarchyPrepareParams,
+    ) -> Result<Option<Vec<CallHierarchyItem>>> {
+        self.client
+            .log_message(MessageType::INFO, "call hierarchy requested!")
+            .await;
+
+        let uri = param.text_document_position_params.text_document.uri;
+        let position = param.text_document_position_params.position;
+
+        self.query(move |db| {
+            let document = db.document(&uri)?;
+            let tokens = db.tokens(&uri)?;
+            let index = LineIndex::new(document.text());
+            let offset = index.offset(position);
+
+            let Some(tokeninfo) = token_at(&tokens, offset) else {
+                return Ok(None);
+            };
+
+            let Token::Identifier(name) = &tokeninfo.token else {
+                return Ok(None);
+            };
+
+            let item = callee(&db, &uri, name, &tokeninfo.range)?;
+
+            Ok(item.map(|item| vec![item]))
+        })
+        .await
+    }
+
+    async fn incoming_calls(
+        &self,
+        param: CallHierarchyIncomingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        let item = param.item;
+
+        // Nothing calls the statements of a file
+        if item.kind == SymbolKind::FILE {
+            return Ok(Some(vec![]));
+        }
+
+        self.query(move |db| {
+            let mut files = vec![item.uri.clone()];
+
+            files.extend(db.files().filter(|file| **file != item.uri).cloned());
+
+            let mut calls = vec![];
+
+            for file in files {
+                // Whether a call to the name in this file runs the item, another file may
+                // declare a function of the same name or not use the item's file at all
+                let runs = match db.symbols(&file)?.lookup(&item.name) {
+                    Some((declared, AstNode::FunctionDeclaration(..))) => {
+                        !is_std(&item) && *declared.unwrap_or(&file) == item.uri
+                    }
+                    _ => is_std(&item),
+                };
+
+                if !runs {
+                    continue;
+                }
+
+                let document = db.document(&file)?;
+                let index = LineIndex::new(document.text());
+
+                let mut top = vec![];
+
+                for node in db.ast(&file)?.iter() {
+                    let ranges = node
+                        .calls()
+                        .into_iter()
+                        .filter(|(name, ..)| *name == item.name)
+                        .map(|(.., span)| range(&index, &span))
+                        .collect::<Vec<Range>>();
+
+                    if ranges.is_empty() {
+                        continue;
+                    }
+
+                    match function_item(&db, &file, node)? {
+                        Some(from) => calls.push(CallHierarchyIncomingCall {
+                            from,
+                            from_ranges: ranges,
+                        }),
+                        None => top.extend(ranges),
+                    }
                 }
-                if let AstNode::FunctionDeclaration(name, args, return_type, _) = f {
-                    functions_docs.insert(
-                        name.to_string(),
-                        format!(
-                            "fn {name}({}): {return_type}",
-                            args.iter()
-                                .map(|(name, r#type)| format!("{name}: {type}"))
-                                .collect::<Vec<String>>()
-                                .join(",")
-                        ),
-                    );
-                    functions.push(name.to_string());
+
+                if !top.is_empty() {
+                    calls.push(CallHierarchyIncomingCall {
+                        from: file_item(&db, &file)?,
+                        from_ranges: top,
+                    });
                 }
-            });
+            }
+
+            Ok(Some(calls))
+        })
+        .await
+    }
+
+    async fn outgoing_calls(
+        &self,
+        param: CallHierarchyOutgoingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        let item = param.item;
+
+        // STD functions are leaves, they call nothing written in calcagebra
+        if is_std(&item) {
+            return Ok(Some(vec![]));
+        }
+
+        self.query(move |db| {
+            let ast = db.ast(&item.uri)?;
 
-        let line = param.text_document_position_params.position.line as usize;
-        let character = param.text_document_position_params.position.character as usize;
-        let line = self.file.get(&line).unwrap().to_string();
-        let mut text = String::new();
-        let mut pos_found = false;
-
-        for (i, char) in line.split("").enumerate() {
-            let c = char.chars().next();
-            if c.is_some() && !c.unwrap().is_ascii_alphanumeric() {
-                if pos_found {
-                    break;
+            let nodes = ast
+                .iter()
+                .filter(|node| match node {
+                    AstNode::FunctionDeclaration(name, ..) => {
+                        item.kind == SymbolKind::FUNCTION && *name == item.name
+                    }
+                    AstNode::Assignment(..) | AstNode::FunctionCall(..) => {
+                        item.kind == SymbolKind::FILE
+                    }
+                    _ => false,
+                })
+                // Calls run the first declaration of a function
+                .take(match item.kind {
+                    SymbolKind::FUNCTION => 1,
+                    _ => usize::MAX,
+                });
+
+            let mut callees: Vec<(&str, Vec<Span>)> = vec![];
+
+            for (name, _, span) in nodes.flat_map(AstNode::calls) {
+                match callees.iter_mut().find(|(callee, _)| *callee == name) {
+                    Some((_, spans)) => spans.push(span),
+                    None => callees.push((name, vec![span])),
                 }
-                text = String::new();
-                continue;
             }
-            text += char;
-            if i == character {
-                pos_found = true;
+
+            let document = db.document(&item.uri)?;
+            let index = LineIndex::new(document.text());
+
+            let mut calls = vec![];
+
+            for (name, spans) in callees {
+                if let Some(to) = callee(&db, &item.uri, name, &spans[0])? {
+                    calls.push(CallHierarchyOutgoingCall {
+                        to,
+                        from_ranges: spans.iter().map(|span| range(&index, span)).collect(),
+                    });
+                }
             }
+
+            Ok(Some(calls))
+        })
+        .await
+    }
+
+    async fn formatting(&self, param: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        self.client
+            .log_message(MessageType::INFO, "formatting requested!")
+            .await;
+
+        let uri = param.text_document.uri;
+
+        let (document, config) = self
+            .query(move |db| Ok((db.document(&uri)?, db.config(&uri))))
+            .await?;
+
+        let indent = match (config.format.indent, param.options.insert_spaces) {
+            (Some(width), _) => " ".repeat(width),
+            (None, true) => " ".repeat(param.options.tab_size as usize),
+            (None, false) => "\t".to_string(),
+        };
+
+        let text = document.text();
+
+        let Some(formatted) = reformat(text, &indent, config.format.spaced_operators) else {
+            return Ok(None);
+        };
+
+        if formatted == text {
+            return Ok(Some(vec![]));
         }
 
-        let response = if functions.binary_search(&text).is_ok() {
-            MarkedString::String(functions_docs.get(&text).unwrap().to_string())
-        } else if std_docs.contains(&text.trim().to_string()) {
-            let type_map = internal_type_map(&text);
+        // The whole document is replaced
+        let end = Position::new(
+            text.matches('\n').count() as u32,
+            text.rsplit('\n')
+                .next()
+                .unwrap_or_default()
+                .encode_utf16()
+                .count() as u32,
+        );
+
+        Ok(Some(vec![TextEdit::new(
+            Range::new(Position::new(0, 0), end),
+            formatted,
+        )]))
+    }
 
-            MarkedString::String(format!(
-                "fn {text}({}): {}",
-                type_map
-                    .0
-                    .iter()
-                    .map(|g| g
-                        .iter()
-                        .map(|h| h.to_string())
-                        .collect::<Vec<String>>()
-                        .join("|"))
-                    .collect::<Vec<String>>()
-                    .join(","),
-                type_map.1
+    async fn folding_range(&self, param: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        let uri = param.text_document.uri;
+
+        self.query(move |db| {
+            let document = db.document(&uri)?;
+            let ast = db.ast(&uri)?;
+
+            Ok(Some(folding_ranges(document.text(), &ast)))
+        })
+        .await
+    }
+
+    async fn selection_range(
+        &self,
+        param: SelectionRangeParams,
+    ) -> Result<Option<Vec<SelectionRange>>> {
+        let uri = param.text_document.uri;
+
+        self.query(move |db| {
+            let document = db.document(&uri)?;
+            let tokens = db.tokens(&uri)?;
+            let ast = db.ast(&uri)?;
+            let index = LineIndex::new(document.text());
+
+            Ok(Some(
+                param
+                    .positions
+                    .into_iter()
+                    .map(|position| {
+                        selection_range(document.text(), &tokens, &ast, index.offset(position))
+                    })
+                    .collect(),
             ))
-        } else if variables.iter().any(|f| f.0 == text) {
-            let (name, r#type) = variables.iter().find(|f| f.0 == text).unwrap();
-            MarkedString::String(format!("{name}: {type}"))
+        })
+        .await
+    }
+
+    async fn signature_help(&self, param: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        self.client
+            .log_message(MessageType::INFO, "signature help requested!")
+            .await;
+
+        let uri = param.text_document_position_params.text_document.uri;
+        let position = param.text_document_position_params.position;
+
+        let (document, symbols) = self
+            .query(move |db| Ok((db.document(&uri)?, db.symbols(&uri)?)))
+            .await?;
+
+        let Some((name, active_parameter)) =
+            Parser::new(&SyntaxNode::parse(document.before(position)))
+                .statements()
+                .last()
+                .and_then(|statement| enclosing_call(statement))
+        else {
+            return Ok(None);
+        };
+
+        let signature = if let Some(AstNode::FunctionDeclaration(_, args, return_type, _, doc, _)) =
+            symbols.function(&name)
+        {
+            let docs = Docs::parse(doc.as_deref().unwrap_or_default());
+
+            signature_information(
+                &name,
+                args.iter()
+                    .map(|(arg, r#type)| {
+                        (
+                            format!("{arg}: {type}"),
+                            docs.param(arg).map(str::to_string),
+                        )
+                    })
+                    .collect(),
+                return_type.to_string(),
+                Docs {
+                    params: vec![],
+                    ..docs
+                },
+            )
+        } else if STD.contains(&name.as_str()) {
+            signature_information(
+                &name,
+                std_params(&name)
+                    .into_iter()
+                    .map(|param| (param, None))
+                    .collect(),
+                internal_type_map(&name).1.to_string(),
+                Docs::default(),
+            )
         } else {
-            MarkedString::String(String::new())
+            return Ok(None);
         };
 
-        Ok(Some(Hover {
-            contents: HoverContents::Array(vec![response]),
-            range: None,
+        Ok(Some(SignatureHelp {
+            signatures: vec![signature],
+            active_signature: Some(0),
+            active_parameter: Some(active_parameter as u32),
         }))
     }
+
+    async fn inlay_hint(&self, param: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        self.client
+            .log_message(MessageType::INFO, "inlay hints requested!")
+            .await;
+
+        let uri = param.text_document.uri;
+        let range = param.range;
+
+        // The evaluation is memoized until an edit changes a token, only the first request
+        // after such an edit runs the worksheet. If it times out it keeps running for the
+        // next request, unless an edit cancels it
+        let worksheet = self.query(move |db| {
+            let document = db.document(&uri)?;
+            let ast = db.ast(&uri)?;
+            let values = db.evaluation(&uri)?;
+
+            let lines = document.text().lines().collect::<Vec<&str>>();
+            let index = LineIndex::new(document.text());
+
+            let mut hints = vec![];
+
+            for (node, value) in ast.iter().zip(values.iter()) {
+                let Some(value) = value else {
+                    continue;
+                };
+
+                // Statements may span several lines, their hint goes after the last one
+                let line = index.position(node.span().end().saturating_sub(1)).line;
+
+                let (label, tooltip) = match value {
+                    Ok(value) => (format!("⇒ {value}"), None),
+                    Err(err @ EvalError::Unresolved(_)) => {
+                        ("⇒ unresolved".to_string(), Some(err.to_string()))
+                    }
+                    Err(err) => (format!("⇒ {err}"), None),
+                };
+
+                hints.push(InlayHint {
+                    position: Position::new(
+                        line,
+                        lines[line as usize].encode_utf16().count() as u32,
+                    ),
+                    label: InlayHintLabel::String(label),
+                    kind: None,
+                    text_edits: None,
+                    tooltip: tooltip.map(InlayHintTooltip::String),
+                    padding_left: Some(true),
+                    padding_right: None,
+                    data: None,
+                });
+            }
+
+            Ok(hints)
+        });
+
+        let hints = match tokio::time::timeout(EVALUATION_TIMEOUT, worksheet).await {
+            Ok(hints) => hints?,
+            Err(_) => {
+                self.client
+                    .log_message(MessageType::WARNING, "worksheet evaluation timed out")
+                    .await;
+
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(
+            hints
+                .into_iter()
+                .filter(|hint| {
+                    hint.position.line >= range.start.line && hint.position.line <= range.end.line
+                })
+                .collect(),
+        ))
+    }
 }
 
 impl Backend {
+    /// Reads the configuration of every workspace folder, one that can't be read is
+    /// reported and replaced by the default one
+    async fn configure(&self) {
+        for root in self.db.roots() {
+            let config = match Config::read(&root) {
+                Ok(config) => config,
+                Err(err) => {
+                    self.client.show_message(MessageType::ERROR, err).await;
+
+                    Config::default()
+                }
+            };
+
+            self.db.set_config(root, config);
+        }
+    }
+
+    /// Runs `query` on a snapshot of the documents off the async runtime, so a slow
+    /// query doesn't hold up other requests. An edit arriving meanwhile cancels it
+    async fn query<T: Send + 'static>(
+        &self,
+        query: impl FnOnce(Snapshot) -> Cancellable<T> + Send + 'static,
+    ) -> Result<T> {
+        let snapshot = self.db.snapshot();
+
+        match tokio::task::spawn_blocking(move || query(snapshot)).await {
+            Ok(result) => Ok(result?),
+            Err(_) => Err(jsonrpc::Error::internal_error()),
+        }
+    }
+
     pub fn get_closest_match(&self, word: &str, words: Vec<String>) -> Vec<String> {
+        if word.is_empty() {
+            return words;
+        }
+
         let engine_options = SearchOptions::new().threshold(0.55);
         let mut engine: SimSearch<u32> = SimSearch::new_with(engine_options);
 
@@ -512,6 +1208,200 @@ impl Backend {
     }
 }
 
+/// Markdown describing a function, STD function or variable: its signature or type, the
+/// doc comment above its declaration and, for variables, the value it evaluates to
+fn documentation(db: &Snapshot, file: &Url, name: &str) -> Cancellable<Option<String>> {
+    if STD.contains(&name) {
+        return Ok(Some(fence(&std_signature(name))));
+    }
+
+    let symbols = db.symbols(file)?;
+
+    if let Some((declared, AstNode::FunctionDeclaration(_, args, return_type, _, doc, _))) =
+        symbols.lookup(name)
+    {
+        let markdown = Docs::parse(doc.as_deref().unwrap_or_default()).markdown(&signature(
+            name,
+            args,
+            return_type,
+        ));
+
+        let ast = db.ast(declared.unwrap_or(file))?;
+
+        return Ok(Some(match Recursion::new(&ast).recursive.get(name) {
+            None => markdown,
+            Some(through) if through.is_empty() => format!("{markdown}\n\n*Recursive*"),
+            Some(through) => format!(
+                "{markdown}\n\n*Mutually recursive with {}*",
+                through
+                    .iter()
+                    .map(|name| format!("`{name}`"))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        }));
+    }
+
+    let (r#type, exact, docs) = match symbols.variable(name) {
+        Some(AstNode::Assignment((_, datatype), expr, doc, _)) => (
+            datatype.unwrap_or(NumberType::Unknown),
+            match expr {
+                Expression::Integer(literal, _) | Expression::Real(literal, _) => {
+                    Some(literal.exact())
+                }
+                _ => None,
+            },
+            Docs::parse(doc.as_deref().unwrap_or_default()),
+        ),
+        _ if ["pi", "π", "e"].contains(&name) => (NumberType::Real, None, Docs::default()),
+        _ if let Some(value) = db.config(file).constants.get(name) => {
+            (value.datatype(), None, Docs::default())
+        }
+        _ => return Ok(None),
+    };
+
+    // Literals are shown exactly, anything else needs the file to run
+    Ok(Some(match exact {
+        Some(exact) => docs.markdown(&format!("{name}: {type} = {exact}")),
+        None => match db.value(file, name)? {
+            Some(value) => docs.markdown(&format!("{name}: {type} = {value}")),
+            None => docs.markdown(&format!("{name}: {type}")),
+        },
+    }))
+}
+
+/// Builds the signature `fn name(params): return_type`, pointing each parameter at its
+/// UTF-16 offsets in the label
+fn signature_information(
+    name: &str,
+    params: Vec<(String, Option<String>)>,
+    return_type: String,
+    docs: Docs,
+) -> SignatureInformation {
+    let mut label = format!("fn {name}(");
+    let mut parameters = vec![];
+
+    for (i, (param, documentation)) in params.into_iter().enumerate() {
+        if i != 0 {
+            label += ", ";
+        }
+
+        let start = label.encode_utf16().count() as u32;
+        label += &param;
+
+        parameters.push(ParameterInformation {
+            label: ParameterLabel::LabelOffsets([start, label.encode_utf16().count() as u32]),
+            documentation: documentation.map(|value| {
+                Documentation::MarkupContent(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value,
+                })
+            }),
+        });
+    }
+
+    label += &format!("): {return_type}");
+
+    let documentation = docs.body();
+
+    SignatureInformation {
+        label,
+        documentation: (!documentation.is_empty()).then_some(Documentation::MarkupContent(
+            MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: documentation,
+            },
+        )),
+        parameters: Some(parameters),
+        active_parameter: None,
+    }
+}
+
+/// The call hierarchy item of what a call to `name` at `span` in `file` runs, a declared
+/// function or a STD one. STD functions are declared nowhere, their item is the call
+fn callee(
+    db: &Snapshot,
+    file: &Url,
+    name: &str,
+    span: &Span,
+) -> Cancellable<Option<CallHierarchyItem>> {
+    if let Some((declared, node @ AstNode::FunctionDeclaration(..))) =
+        db.symbols(file)?.lookup(name)
+    {
+        return function_item(db, declared.unwrap_or(file), node);
+    }
+
+    if !STD.contains(&name) {
+        return Ok(None);
+    }
+
+    let document = db.document(file)?;
+    let range = range(&LineIndex::new(document.text()), span);
+
+    Ok(Some(CallHierarchyItem {
+        name: name.to_string(),
+        kind: SymbolKind::FUNCTION,
+        tags: None,
+        detail: Some(std_detail(name)),
+        uri: file.clone(),
+        range,
+        selection_range: range,
+        data: Some(json!({ "std": true })),
+    }))
+}
+
+/// The call hierarchy item of a function declared in `file`, `None` for other statements
+fn function_item(
+    db: &Snapshot,
+    file: &Url,
+    node: &AstNode,
+) -> Cancellable<Option<CallHierarchyItem>> {
+    let AstNode::FunctionDeclaration(name, args, return_type, .., spans) = node else {
+        return Ok(None);
+    };
+
+    let document = db.document(file)?;
+    let index = LineIndex::new(document.text());
+
+    Ok(Some(CallHierarchyItem {
+        name: name.clone(),
+        kind: SymbolKind::FUNCTION,
+        tags: None,
+        detail: Some(detail(args, return_type)),
+        uri: file.clone(),
+        range: range(&index, &spans.node),
+        selection_range: range(&index, &spans.name),
+        data: None,
+    }))
+}
+
+/// The call hierarchy item standing for the statements of `file` outside any function
+fn file_item(db: &Snapshot, file: &Url) -> Cancellable<CallHierarchyItem> {
+    let document = db.document(file)?;
+    let end = Position::new(document.text().matches('\n').count() as u32 + 1, 0);
+
+    Ok(CallHierarchyItem {
+        name: name(file).to_string(),
+        kind: SymbolKind::FILE,
+        tags: None,
+        detail: None,
+        uri: file.clone(),
+        range: Range::new(Position::new(0, 0), end),
+        selection_range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+        data: None,
+    })
+}
+
+fn is_std(item: &CallHierarchyItem) -> bool {
+    item.data
+        .as_ref()
+        .is_some_and(|data| data["std"] == json!(true))
+}
+
+fn range(index: &LineIndex, span: &Span) -> Range {
+    Range::new(index.position(*span.start()), index.position(*span.end()))
+}
+
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt().init();
@@ -520,7 +1410,8 @@ async fn main() {
 
     let (service, socket) = LspService::new(|client| Backend {
         client,
-        file: DashMap::new(),
+        db: Arc::new(Database::default()),
+        token_hover: Arc::new(AtomicBool::new(false)),
     });
     Server::new(stdin, stdout, socket).serve(service).await;
 }
@@ -0,0 +1,267 @@
+use std::{fmt::Display, num::IntErrorKind, ops::Neg};
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+/// Largest power of ten a decimal literal is scaled by, `1e10000` or `1e-10000`
+pub const MAX_SCALE: u32 = 10_000;
+
+/// A numeric literal as written in the source together with its exact value
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Literal {
+    pub text: String,
+    pub value: BigRational,
+}
+
+impl Literal {
+    /// Parses decimal (`1_000`, `0.25`, `1.5e-3`), hexadecimal (`0xFF`) and
+    /// binary (`0b1010`) literals. Decimal ones are scaled by at most `10^MAX_SCALE`
+    /// either way, the exact value of a larger exponent takes too long to compute
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid number `{text}`");
+        let out_of_range = || format!("exponent of `{text}` is out of range");
+        let digits = text.replace('_', "");
+
+        let radix = match digits.get(..2) {
+            Some("0x" | "0X") => 16,
+            Some("0b" | "0B") => 2,
+            _ => 10,
+        };
+
+        let value = if radix != 10 {
+            BigRational::from_integer(
+                BigInt::parse_bytes(&digits.as_bytes()[2..], radix).ok_or_else(invalid)?,
+            )
+        } else {
+            let (mantissa, exponent) = match digits.split_once(['e', 'E']) {
+                Some((mantissa, exponent)) => (
+                    mantissa,
+                    exponent.parse::<i32>().map_err(|err| match err.kind() {
+                        IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => out_of_range(),
+                        _ => invalid(),
+                    })?,
+                ),
+                None => (digits.as_str(), 0),
+            };
+
+            let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+            if integer.is_empty() && fraction.is_empty()
+                || !integer
+                    .chars()
+                    .chain(fraction.chars())
+                    .all(|c| c.is_ascii_digit())
+            {
+                return Err(invalid());
+            }
+
+            let numerator = BigInt::parse_bytes(format!("{integer}{fraction}").as_bytes(), 10)
+                .ok_or_else(invalid)?;
+
+            let scale = i32::try_from(fraction.len())
+                .ok()
+                .and_then(|digits| exponent.checked_sub(digits))
+                .filter(|scale| scale.unsigned_abs() <= MAX_SCALE)
+                .ok_or_else(out_of_range)?;
+
+            let power = num_traits::pow(BigInt::from(10), scale.unsigned_abs() as usize);
+
+            if scale < 0 {
+                BigRational::new(numerator, power)
+            } else {
+                BigRational::from_integer(numerator * power)
+            }
+        };
+
+        Ok(Self {
+            text: text.to_string(),
+            value,
+        })
+    }
+
+    /// Parses an imaginary literal such as `2i` or `i`, keeping the coefficient as the value
+    pub fn imaginary(text: &str) -> Result<Self, String> {
+        let coefficient = text
+            .strip_suffix('i')
+            .ok_or_else(|| format!("invalid number `{text}`"))?;
+
+        let value = if coefficient.is_empty() {
+            BigRational::one()
+        } else {
+            Self::parse(coefficient)?.value
+        };
+
+        Ok(Self {
+            text: text.to_string(),
+            value,
+        })
+    }
+
+    /// Whether the literal is written as a real number, with a decimal point or an exponent
+    pub fn is_real(&self) -> bool {
+        let hex = matches!(self.text.get(..2), Some("0x" | "0X"));
+
+        self.text.contains('.') || (!hex && self.text.contains(['e', 'E']))
+    }
+
+    pub fn to_i32(&self) -> Option<i32> {
+        if !self.value.is_integer() {
+            return None;
+        }
+
+        self.value.to_integer().to_i32()
+    }
+
+    pub fn to_f32(&self) -> f32 {
+        self.value.to_f32().unwrap_or(f32::NAN)
+    }
+
+    /// Exact decimal expansion of the literal, falling back to a fraction when
+    /// the expansion does not terminate
+    pub fn exact(&self) -> String {
+        let (numerator, mut denominator) = (self.value.numer().abs(), self.value.denom().clone());
+        let sign = if self.value.is_negative() { "-" } else { "" };
+
+        let mut digits = 0;
+        let ten = BigInt::from(10);
+
+        for factor in [BigInt::from(2), BigInt::from(5)] {
+            while (&denominator % &factor).is_zero() {
+                denominator /= &factor;
+                digits += 1;
+            }
+        }
+
+        if !denominator.is_one() {
+            return format!("{sign}{numerator}/{}", self.value.denom());
+        }
+
+        let scaled = &numerator * num_traits::pow(ten, digits) / self.value.denom();
+        let scaled = format!("{scaled:0>width$}", width = digits + 1);
+        let (integer, fraction) = scaled.split_at(scaled.len() - digits);
+        let fraction = fraction.trim_end_matches('0');
+
+        if fraction.is_empty() {
+            format!("{sign}{integer}")
+        } else {
+            format!("{sign}{integer}.{fraction}")
+        }
+    }
+}
+
+impl Neg for Literal {
+    type Output = Literal;
+
+    fn neg(self) -> Self::Output {
+        Self {
+            text: format!("-{}", self.text),
+            value: -self.value,
+        }
+    }
+}
+
+impl Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(text: &str) -> BigRational {
+        Literal::parse(text).unwrap().value
+    }
+
+    fn ratio(numer: i64, denom: i64) -> BigRational {
+        BigRational::new(numer.into(), denom.into())
+    }
+
+    #[test]
+    fn decimal() {
+        assert_eq!(value("1_000_000"), ratio(1_000_000, 1));
+        assert_eq!(value("0.1"), ratio(1, 10));
+        assert_eq!(value(".5"), ratio(1, 2));
+        assert!(Literal::parse("0.1").unwrap().is_real());
+        assert!(!Literal::parse("12").unwrap().is_real());
+    }
+
+    #[test]
+    fn scientific() {
+        assert_eq!(value("1.5e-3"), ratio(3, 2000));
+        assert_eq!(value("2E3"), ratio(2000, 1));
+        assert_eq!(value("1e10"), ratio(10_000_000_000, 1));
+        assert!(Literal::parse("1e3").unwrap().is_real());
+        assert_eq!(Literal::parse("1.5e-3").unwrap().exact(), "0.0015");
+        assert_eq!(Literal::parse("1e10").unwrap().exact(), "10000000000");
+    }
+
+    #[test]
+    fn hexadecimal_and_binary() {
+        assert_eq!(value("0xFF"), ratio(255, 1));
+        assert_eq!(value("0x1e"), ratio(30, 1));
+        assert_eq!(value("0b1010"), ratio(10, 1));
+        assert_eq!(value("0b1111_0000"), ratio(240, 1));
+        assert!(!Literal::parse("0x1e").unwrap().is_real());
+    }
+
+    #[test]
+    fn invalid() {
+        for text in ["0x", "0b102", "1.2.3", "1e", "1e+", "."] {
+            assert_eq!(
+                Literal::parse(text),
+                Err(format!("invalid number `{text}`")),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn exponent_out_of_range() {
+        assert!(Literal::parse("1e10000").is_ok());
+        assert!(Literal::parse("1e-10000").is_ok());
+
+        for text in ["1e10001", "1e-10001", "0.5e-10000", "1e99999999999"] {
+            assert_eq!(
+                Literal::parse(text),
+                Err(format!("exponent of `{text}` is out of range")),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn integer_range() {
+        assert_eq!(
+            Literal::parse("2147483647").unwrap().to_i32(),
+            Some(i32::MAX)
+        );
+        assert_eq!(Literal::parse("2147483648").unwrap().to_i32(), None);
+        assert_eq!(
+            (-Literal::parse("2147483648").unwrap()).to_i32(),
+            Some(i32::MIN)
+        );
+        assert_eq!(Literal::parse("0.5").unwrap().to_i32(), None);
+    }
+
+    #[test]
+    fn exact() {
+        let third = Literal {
+            text: "1/3".to_string(),
+            value: ratio(1, 3),
+        };
+
+        assert_eq!(third.exact(), "1/3");
+        assert_eq!((-Literal::parse("0.25").unwrap()).exact(), "-0.25");
+        assert_eq!(Literal::parse("0.1").unwrap().to_f32(), 0.1);
+    }
+
+    #[test]
+    fn imaginary() {
+        assert_eq!(Literal::imaginary("i").unwrap().value, ratio(1, 1));
+        assert_eq!(Literal::imaginary("2.5i").unwrap().value, ratio(5, 2));
+        assert!(Literal::imaginary("1e10001i").is_err());
+    }
+}
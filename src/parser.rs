@@ -1,7 +1,10 @@
 use std::{iter::Peekable, ops::RangeInclusive, slice::Iter};
 
 use crate::{
-    ast::{AstNode, Expression},
+    ast::{AstNode, Expression, Lint, Spans},
+    constant,
+    evaluator::{self, EvalError, Value},
+    syntax::{SyntaxElement, SyntaxKind, SyntaxNode},
     token::{Token, TokenInfo},
     types::NumberType,
 };
@@ -11,44 +14,143 @@ pub struct Parser {
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Vec<TokenInfo>>) -> Self {
+    /// Reads the statements off the syntax tree, as the tokens the lexer produces for them
+    pub fn new(root: &SyntaxNode) -> Self {
+        let tokens = root
+            .children_with_tokens()
+            .filter_map(|child| match child {
+                SyntaxElement::Node(statement) => Some(Self::significant(&statement)),
+                SyntaxElement::Token(token) if token.kind() == SyntaxKind::DocComment => {
+                    Some(vec![token.info()])
+                }
+                SyntaxElement::Token(_) => None,
+            })
+            .collect();
+
         Self { tokens }
     }
 
+    /// The tokens of a statement without its trivia, with the `*` implied in `2x` put
+    /// back where the lexer puts it
+    fn significant(statement: &SyntaxNode) -> Vec<TokenInfo> {
+        let mut tokens: Vec<TokenInfo> = vec![];
+        let mut newline = false;
+
+        for token in statement.tokens() {
+            if token.kind().is_trivia() {
+                newline |= token.kind() == SyntaxKind::Newline;
+                continue;
+            }
+
+            let tokeninfo = token.info();
+
+            if let Some(Token::Integer(_) | Token::Float(_)) = tokens.last().map(|t| &t.token)
+                && let Token::Identifier(_) | Token::Imaginary(_) = tokeninfo.token
+                && !newline
+            {
+                let end = *tokens.last().unwrap().range.end();
+
+                tokens.push(TokenInfo::new(Token::Mul, end..=end));
+            }
+
+            newline = false;
+            tokens.push(tokeninfo);
+        }
+
+        tokens
+    }
+
+    pub fn statements(&self) -> &[Vec<TokenInfo>] {
+        &self.tokens
+    }
+
+    /// Parses every statement on its own, together with the doc comments above it, so
+    /// that one incomplete statement doesn't lose the rest of the document
+    pub fn partial_ast(&self) -> Vec<AstNode> {
+        let mut docs = vec![];
+
+        self.tokens
+            .iter()
+            .flat_map(|statement| {
+                if let Token::DocComment(_) = statement[0].token {
+                    docs.push(statement.clone());
+                    return vec![];
+                }
+
+                docs.push(statement.clone());
+
+                Self {
+                    tokens: std::mem::take(&mut docs),
+                }
+                .ast()
+                .unwrap_or_default()
+            })
+            .collect()
+    }
+
     pub fn ast(&self) -> Option<Vec<AstNode>> {
         let mut ast = vec![];
+        let mut docs = vec![];
         let lines = &self.tokens;
 
         'main: for line in lines {
+            if let Token::DocComment(comment) = &line[0].token {
+                docs.push(comment.to_string());
+                continue;
+            }
+
+            // Doc comments belong to the declaration directly after them
+            let doc = (!docs.is_empty()).then(|| docs.join("\n"));
+            docs.clear();
+
+            if let Some((message, range)) =
+                line.iter().find_map(|tokeninfo| match &tokeninfo.token {
+                    Token::Error(message) => Some((message, &tokeninfo.range)),
+                    _ => None,
+                })
+            {
+                ast.push(AstNode::Error(message.to_string(), range.clone()));
+                continue;
+            }
+
             let mut tokens = line.iter().peekable();
 
             let identifier = tokens.next()?;
 
+            let node = *identifier.range.start()..=*line.last()?.range.end();
+
             match identifier.token {
                 Token::Let => {
                     let mut datatype = None;
+                    let mut annotation = None;
+
+                    let tokeninfo = tokens.next()?;
+                    let name_span = tokeninfo.range.clone();
+
+                    let name = match Self::name(tokeninfo) {
+                        Ok(name) => name,
+                        Err((message, range)) => {
+                            ast.push(AstNode::Error(message, range));
 
-                    let name = match &tokens.next()?.token {
-                        Token::Identifier(name) => name,
-                        _ => unreachable!(),
+                            continue;
+                        }
                     };
 
                     if tokens.peek()?.token == Token::Colon {
                         tokens.next();
 
-                        if let Token::Identifier(ident) = &tokens.peek()?.token {
-                            tokens.next();
-
-                            datatype = Some(NumberType::parse(ident))
-                        } else {
-                            let tokeninfo = tokens.next()?;
+                        let tokeninfo = tokens.next()?;
 
-                            ast.push(AstNode::Error(
-                                "Syntax Error: expected datatype after `:`".to_string(),
-                                tokeninfo.range.clone(),
-                            ));
+                        match Self::datatype(tokeninfo) {
+                            Ok(r#type) => {
+                                datatype = Some(r#type);
+                                annotation = Some(tokeninfo.range.clone());
+                            }
+                            Err((message, range)) => {
+                                ast.push(AstNode::Error(message, range));
 
-                            continue;
+                                continue;
+                            }
                         }
                     }
 
@@ -57,7 +159,13 @@ impl Parser {
                         continue;
                     }
 
-                    let (expr, _, range) = self.pratt_parser(tokens, 0);
+                    let (expr, _, range) = self.pratt_parser(tokens, 0, *node.end());
+
+                    let value = self.fold(&expr, &range, &mut ast);
+
+                    if self.check(&expr, &mut ast) {
+                        continue;
+                    }
 
                     let expr_type = expr.infer_datatype();
 
@@ -65,31 +173,59 @@ impl Parser {
                         datatype = expr_type
                     }
 
-                    if let Some(expression_type) = expr_type {
-                        if expr_type? != datatype? {
-                            ast.push(AstNode::Error(
-                                format!(
-                                    "Type Error: expected {} found {expression_type}",
-                                    datatype?
-                                ),
-                                range,
-                            ));
+                    if let Some(expression_type) = expr_type
+                        && expression_type.base() != datatype?.base()
+                    {
+                        ast.push(AstNode::Error(
+                            format!("Type Error: expected {} found {expression_type}", datatype?),
+                            range,
+                        ));
 
-                            continue;
-                        }
+                        continue;
+                    }
+
+                    // `N` and `Q` are checked against the value when it is known
+                    if let Some(value) = value
+                        && evaluator::belongs(value.clone(), &datatype?.to_string())
+                            == Ok(Value::Bool(false))
+                    {
+                        ast.push(AstNode::Error(
+                            format!("Type Error: expected {} found {value}", datatype?),
+                            range,
+                        ));
+
+                        continue;
                     }
 
-                    ast.push(AstNode::Assignment((name.to_string(), datatype), expr));
+                    ast.push(AstNode::Assignment(
+                        (name.to_string(), datatype),
+                        expr,
+                        doc,
+                        Spans {
+                            name: name_span,
+                            annotation,
+                            params: vec![],
+                            node,
+                        },
+                    ));
                 }
                 Token::Fn => {
-                    let name = match &tokens.next()?.token {
-                        Token::Identifier(name) => name,
-                        _ => unreachable!(),
+                    let tokeninfo = tokens.next()?;
+                    let name_span = tokeninfo.range.clone();
+
+                    let name = match Self::name(tokeninfo) {
+                        Ok(name) => name,
+                        Err((message, range)) => {
+                            ast.push(AstNode::Error(message, range));
+
+                            continue;
+                        }
                     };
 
                     tokens.next(); // `(`
 
                     let mut args = vec![];
+                    let mut params = vec![];
 
                     loop {
                         let t = tokens.peek();
@@ -105,52 +241,64 @@ impl Parser {
 
                         let t = tokens.next()?;
 
-                        let mut datatype = Some(NumberType::Real);
+                        // Types not written are inferred later, falling back to the
+                        // configured default
+                        let mut datatype = Some(NumberType::Unknown);
+                        let mut annotation = None;
 
                         if tokens.peek()?.token == Token::Colon {
                             tokens.next();
 
-                            if let Token::Identifier(ident) = &tokens.peek()?.token {
-                                tokens.next();
+                            let tokeninfo = tokens.next()?;
+
+                            match Self::datatype(tokeninfo) {
+                                Ok(r#type) => {
+                                    datatype = Some(r#type);
+                                    annotation = Some(tokeninfo.range.clone());
+                                }
+                                Err((message, range)) => {
+                                    ast.push(AstNode::Error(message, range));
+
+                                    continue 'main;
+                                }
+                            }
+                        }
 
-                                datatype = Some(NumberType::parse(ident))
-                            } else {
-                                let tokeninfo = tokens.next()?;
+                        if t.token == Token::Comma {
+                            continue;
+                        }
 
-                                ast.push(AstNode::Error(
-                                    "Syntax Error: expected datatype after `:`".to_string(),
-                                    tokeninfo.range.clone(),
-                                ));
+                        match Self::name(t) {
+                            Ok(name) => {
+                                args.push((name.to_string(), datatype?));
+                                params.push((t.range.clone(), annotation));
+                            }
+                            Err((message, range)) => {
+                                ast.push(AstNode::Error(message, range));
 
                                 continue 'main;
                             }
                         }
-
-                        match &t.token {
-                            Token::Identifier(i) => args.push((i.to_string(), datatype?)),
-                            Token::Comma => {}
-                            _ => unreachable!(),
-                        };
                     }
 
-                    let mut return_type = Some(NumberType::Real);
+                    let mut return_type = Some(NumberType::Unknown);
+                    let mut annotation = None;
 
                     if tokens.peek()?.token == Token::Colon {
                         tokens.next();
 
-                        if let Token::Identifier(ident) = &tokens.peek()?.token {
-                            tokens.next();
-
-                            return_type = Some(NumberType::parse(ident))
-                        } else {
-                            let tokeninfo = tokens.next()?;
+                        let tokeninfo = tokens.next()?;
 
-                            ast.push(AstNode::Error(
-                                "Syntax Error: expected datatype after `:`".to_string(),
-                                tokeninfo.range.clone(),
-                            ));
+                        match Self::datatype(tokeninfo) {
+                            Ok(r#type) => {
+                                return_type = Some(r#type);
+                                annotation = Some(tokeninfo.range.clone());
+                            }
+                            Err((message, range)) => {
+                                ast.push(AstNode::Error(message, range));
 
-                            continue;
+                                continue;
+                            }
                         }
                     }
 
@@ -159,22 +307,30 @@ impl Parser {
                         continue;
                     }
 
-                    let (expr, _, range) = self.pratt_parser(tokens, 0);
+                    let (expr, _, range) = self.pratt_parser(tokens, 0, *node.end());
+
+                    self.fold(&expr, &range, &mut ast);
+
+                    if self.check(&expr, &mut ast) {
+                        continue;
+                    }
 
                     let expr_type = expr.infer_datatype();
 
-                    if let Some(expression_type) = expr_type {
-                        if expr_type? != return_type? {
-                            ast.push(AstNode::Error(
-                                format!(
-                                    "Type Error: expected {} found {expression_type}",
-                                    return_type?
-                                ),
-                                range,
-                            ));
+                    // An unwritten return type is whatever inference makes of the body
+                    if let Some(expression_type) = expr_type
+                        && annotation.is_some()
+                        && expression_type.base() != return_type?.base()
+                    {
+                        ast.push(AstNode::Error(
+                            format!(
+                                "Type Error: expected {} found {expression_type}",
+                                return_type?
+                            ),
+                            range,
+                        ));
 
-                            continue;
-                        }
+                        continue;
                     }
 
                     ast.push(AstNode::FunctionDeclaration(
@@ -182,14 +338,71 @@ impl Parser {
                         args,
                         return_type?,
                         expr,
+                        doc,
+                        Spans {
+                            name: name_span,
+                            annotation,
+                            params,
+                            node,
+                        },
                     ));
                 }
+                Token::Use => match tokens.next() {
+                    Some(TokenInfo {
+                        token: Token::Str(path),
+                        range,
+                    }) => {
+                        if let Some(tokeninfo) = tokens.next() {
+                            ast.push(AstNode::Error(
+                                format!(
+                                    "Syntax Error: unexpected `{}` after the path",
+                                    tokeninfo.token
+                                ),
+                                *tokeninfo.range.start()..=*node.end(),
+                            ));
+
+                            continue;
+                        }
+
+                        ast.push(AstNode::Use(
+                            path.to_string(),
+                            Spans {
+                                name: range.clone(),
+                                annotation: None,
+                                params: vec![],
+                                node,
+                            },
+                        ));
+                    }
+                    _ => ast.push(AstNode::Error(
+                        "Syntax Error: expected a path in quotes after `use`".to_string(),
+                        node,
+                    )),
+                },
                 _ => {
                     if !line.is_empty() {
-                        let args = self.pratt_parser(line.iter().peekable(), 0).0;
+                        let (args, _, range) =
+                            self.pratt_parser(line.iter().peekable(), 0, *node.end());
+
+                        self.fold(&args, &range, &mut ast);
 
-                        if let Expression::FunctionCall(name, args) = args {
-                            ast.push(AstNode::FunctionCall(name.to_string(), args))
+                        if self.check(&args, &mut ast) {
+                            continue;
+                        }
+
+                        if let Expression::FunctionCall(name, args, _) = args {
+                            let name_span = identifier.range.clone();
+
+                            ast.push(AstNode::FunctionCall(
+                                name.to_string(),
+                                args,
+                                Spans {
+                                    name: name_span,
+                                    annotation: None,
+                                    params: vec![],
+                                    node,
+                                },
+                            ))
                         }
                     }
                 }
@@ -198,17 +411,20 @@ impl Parser {
         Some(ast)
     }
 
+    /// Parses the expression at the start of `tokens`, an `Error` at offset `at` when
+    /// there is none
     pub fn pratt_parser<'b>(
         &'b self,
         mut tokens: Peekable<Iter<'b, TokenInfo>>,
         prec: u16,
+        at: usize,
     ) -> (
         Expression,
         Peekable<Iter<'b, TokenInfo>>,
         RangeInclusive<usize>,
     ) {
         if tokens.peek().is_none() {
-            return (Expression::Error, tokens, 0..=0)
+            return (Expression::Error(at..=at), tokens, at..=at);
         }
 
         let tokeninfo = &tokens.next().unwrap();
@@ -225,24 +441,23 @@ impl Parser {
                 // or simply an identifier, eg read(), a|b|, c
 
                 if tokens.peek().is_some()
-                    && self.infix_binding_power(&tokens.peek().unwrap().token) == (0, 0)
+                    && Self::infix_binding_power(&tokens.peek().unwrap().token) == (0, 0)
                     && ![Token::RParen, Token::Abs].contains(&tokens.peek().unwrap().token)
                 {
-                    (expr, tokens, end) = self.parse_fn(tokens, i.clone());
+                    (expr, tokens, end) = self.parse_fn(tokens, i.clone(), start);
                 } else {
                     end = *tokeninfo.range.end();
-                    expr = Some(Expression::Identifier(i.to_string()))
+                    expr = Some(Expression::Identifier(i.to_string(), start..=end))
                 };
             }
             Token::LParen => {
                 let exp;
                 let range;
 
-                (exp, tokens, range) = self.pratt_parser(tokens, 0);
+                (exp, tokens, range) = self.pratt_parser(tokens, 0, end);
 
-                end = *range.end();
+                end = tokens.next().map_or(*range.end(), |t| *t.range.end());
                 expr = Some(exp);
-                tokens.next();
             }
             Token::LSquare => {
                 let mut matrix = vec![];
@@ -264,7 +479,11 @@ impl Parser {
                         if !row_tokens.is_empty() {
                             let exp;
 
-                            (exp, _, _) = self.pratt_parser(row_tokens.iter().peekable(), 0);
+                            (exp, _, _) = self.pratt_parser(
+                                row_tokens.iter().peekable(),
+                                0,
+                                *t.range.start(),
+                            );
 
                             row.push(exp);
                         }
@@ -278,7 +497,11 @@ impl Parser {
                         if !row_tokens.is_empty() {
                             let exp;
 
-                            (exp, _, _) = self.pratt_parser(row_tokens.iter().peekable(), 0);
+                            (exp, _, _) = self.pratt_parser(
+                                row_tokens.iter().peekable(),
+                                0,
+                                *t.range.start(),
+                            );
 
                             row.push(exp);
                             row_tokens.clear();
@@ -292,7 +515,8 @@ impl Parser {
                     if t.token == Token::Comma {
                         let exp;
 
-                        (exp, _, _) = self.pratt_parser(row_tokens.iter().peekable(), 0);
+                        (exp, _, _) =
+                            self.pratt_parser(row_tokens.iter().peekable(), 0, *t.range.start());
 
                         row.push(exp);
                         row_tokens.clear();
@@ -303,39 +527,45 @@ impl Parser {
                     row_tokens.push((*t).clone());
                 }
 
-                expr = Some(Expression::Matrix(matrix));
+                expr = Some(Expression::Matrix(matrix, start..=end));
             }
             Token::Abs => {
                 let exp;
                 let range;
 
-                (exp, tokens, range) = self.pratt_parser(tokens, 0);
+                (exp, tokens, range) = self.pratt_parser(tokens, 0, end);
 
-                end = *range.end();
-                expr = Some(Expression::Abs(Box::new(exp)));
-                tokens.next();
+                end = tokens.next().map_or(*range.end(), |t| *t.range.end());
+                expr = Some(Expression::Abs(Box::new(exp), start..=end));
             }
             Token::If => {
-                (expr, tokens, end) = self.parse_if(tokens);
+                (expr, tokens, end) = self.parse_if(tokens, start);
             }
-            Token::Sub => {
-                if let Token::Integer(i) = tokens.peek().unwrap().token {
-                    expr = Some(Expression::Integer(-i));
+            Token::Sub => match tokens.peek().map(|t| &t.token) {
+                Some(Token::Integer(i)) => {
                     end = *tokens.next().unwrap().range.end();
-                } else if let Token::Float(i) = tokens.peek().unwrap().token {
-                    expr = Some(Expression::Real(-i));
+                    expr = Some(Expression::Integer(-i.clone(), start..=end));
+                }
+                Some(Token::Float(i)) => {
                     end = *tokens.next().unwrap().range.end();
-                } else {
+                    expr = Some(Expression::Real(-i.clone(), start..=end));
+                }
+                Some(Token::Imaginary(i)) => {
+                    end = *tokens.next().unwrap().range.end();
+                    expr = Some(Expression::Imaginary(-i.clone(), start..=end));
+                }
+                _ => {
                     end = *tokeninfo.range.end();
                 }
-            }
+            },
             Token::Integer(n) => {
-                expr = Some(Expression::Integer(*n));
-                end = *tokeninfo.range.end();
+                expr = Some(Expression::Integer(n.clone(), start..=end));
             }
             Token::Float(n) => {
-                expr = Some(Expression::Real(*n));
-                end = *tokeninfo.range.end();
+                expr = Some(Expression::Real(n.clone(), start..=end));
+            }
+            Token::Imaginary(n) => {
+                expr = Some(Expression::Imaginary(n.clone(), start..=end));
             }
             _ => {
                 end = *tokeninfo.range.end();
@@ -349,7 +579,7 @@ impl Parser {
                 break;
             }
 
-            let (lbp, rbp) = self.infix_binding_power(&op.unwrap().token);
+            let (lbp, rbp) = Self::infix_binding_power(&op.unwrap().token);
 
             if lbp < prec {
                 break;
@@ -360,23 +590,29 @@ impl Parser {
             let rhs;
             let range;
 
-            (rhs, tokens, range) = self.pratt_parser(tokens, rbp);
+            (rhs, tokens, range) = self.pratt_parser(tokens, rbp, *op.range.end());
 
             end = *range.end();
             expr = Some(Expression::Binary(
-                Box::new(expr.unwrap()),
+                Box::new(expr.unwrap_or(Expression::Error(start..=*op.range.start()))),
                 op.token.clone(),
                 Box::new(rhs),
+                start..=end,
             ));
         }
 
-        (expr.unwrap(), tokens, start..=end)
+        (
+            expr.unwrap_or(Expression::Error(start..=end)),
+            tokens,
+            start..=end,
+        )
     }
 
     pub fn parse_fn<'b>(
         &'b self,
         mut tokens: Peekable<Iter<'b, TokenInfo>>,
         i: String,
+        start: usize,
     ) -> (Option<Expression>, Peekable<Iter<'b, TokenInfo>>, usize) {
         let mut depth = 0;
         let mut params = vec![];
@@ -401,7 +637,7 @@ impl Parser {
                 if depth == 0 {
                     if !expression.is_empty() && depth == 0 {
                         let lex = expression.iter().peekable();
-                        let data = self.pratt_parser(lex, 0).0;
+                        let data = self.pratt_parser(lex, 0, *tokeninfo.range.start()).0;
 
                         params.push(data);
                         expression.clear();
@@ -411,13 +647,17 @@ impl Parser {
                 depth -= 1;
             }
 
-            if *token == Token::LParen {
+            if *token == Token::LParen || *token == Token::LSquare {
                 depth += 1;
             }
 
+            if *token == Token::RSquare {
+                depth -= 1;
+            }
+
             if *token == Token::Comma && depth == 0 {
                 let lex = expression.iter().peekable();
-                let data = self.pratt_parser(lex, 0).0;
+                let data = self.pratt_parser(lex, 0, *tokeninfo.range.start()).0;
 
                 params.push(data);
 
@@ -429,14 +669,14 @@ impl Parser {
         }
         if !expression.is_empty() {
             let lex = expression.iter().peekable();
-            let data = self.pratt_parser(lex, 0).0;
+            let data = self.pratt_parser(lex, 0, end).0;
 
             params.push(data);
             expression.clear();
         }
 
         (
-            Some(Expression::FunctionCall(i.to_string(), params)),
+            Some(Expression::FunctionCall(i.to_string(), params, start..=end)),
             tokens,
             end,
         )
@@ -445,12 +685,14 @@ impl Parser {
     pub fn parse_if<'b>(
         &'b self,
         mut tokens: Peekable<Iter<'b, TokenInfo>>,
+        start: usize,
     ) -> (Option<Expression>, Peekable<Iter<'b, TokenInfo>>, usize) {
         let mut depth = 1;
         let mut params = vec![];
         let mut expression = vec![];
 
-        let mut end = *tokens.peek().unwrap().range.end();
+        // Right after the `if` when nothing follows it
+        let mut end = tokens.peek().map_or(start + 2, |t| *t.range.end());
 
         loop {
             let tokeninfo = tokens.next();
@@ -467,7 +709,7 @@ impl Parser {
 
             if *token == Token::Then || *token == Token::Else {
                 let lex = expression.iter().peekable();
-                let data = self.pratt_parser(lex, 0).0;
+                let data = self.pratt_parser(lex, 0, *tokeninfo.range.start()).0;
 
                 params.push(data);
                 expression.clear();
@@ -490,37 +732,310 @@ impl Parser {
 
         if !expression.is_empty() {
             let lex = expression.iter().peekable();
-            let data = self.pratt_parser(lex, 0).0;
+            let data = self.pratt_parser(lex, 0, end).0;
 
             params.push(data);
             expression.clear();
         }
 
+        // An `if` still being written is missing some of its branches
+        params.resize(3, Expression::Error(end..=end));
+
         (
             Some(Expression::Branched(
                 Box::new(params[0].clone()),
                 Box::new(params[1].clone()),
                 Box::new(params[2].clone()),
+                start..=end,
             )),
             tokens,
             end,
         )
     }
 
-    fn infix_binding_power(&self, op: &Token) -> (u16, u16) {
+    /// Reads the name a `let`, a `fn` or a parameter declares
+    fn name(tokeninfo: &TokenInfo) -> Result<&str, (String, RangeInclusive<usize>)> {
+        match &tokeninfo.token {
+            Token::Identifier(name) => Ok(name),
+            Token::Imaginary(literal) if literal.to_string() == "i" => Err((
+                "Syntax Error: `i` is the imaginary unit, it can't be a name".to_string(),
+                tokeninfo.range.clone(),
+            )),
+            token => Err((
+                format!("Syntax Error: expected a name found `{token}`"),
+                tokeninfo.range.clone(),
+            )),
+        }
+    }
+
+    /// Reads the type written after `:` in an annotation
+    fn datatype(tokeninfo: &TokenInfo) -> Result<NumberType, (String, RangeInclusive<usize>)> {
+        match &tokeninfo.token {
+            Token::Identifier(ident) => NumberType::parse(ident).ok_or_else(|| {
+                (
+                    format!("Type Error: unknown type `{ident}`"),
+                    tokeninfo.range.clone(),
+                )
+            }),
+            _ => Err((
+                "Syntax Error: expected datatype after `:`".to_string(),
+                tokeninfo.range.clone(),
+            )),
+        }
+    }
+
+    /// Reports the errors constant folding can prove will happen at runtime, returning
+    /// the value when the whole expression is constant
+    fn fold(
+        &self,
+        expr: &Expression,
+        range: &RangeInclusive<usize>,
+        ast: &mut Vec<AstNode>,
+    ) -> Option<Value> {
+        let mut errors = vec![];
+
+        let value = constant::fold(expr, &mut errors);
+
+        for err in errors {
+            let lint = match err {
+                EvalError::DivisionByZero => Lint::DivisionByZero,
+                EvalError::Domain(_) => Lint::Domain,
+                _ => Lint::Overflow,
+            };
+
+            ast.push(AstNode::Warning(
+                lint,
+                format!("Warning: {err}"),
+                range.clone(),
+            ));
+        }
+
+        value
+    }
+
+    /// Reports operators the types prove misused: `E` without a number on its left and a
+    /// number set on its right, `Bool` operands of arithmetic or `|`, `if` conditions that
+    /// aren't `Bool` and integer literals outside of Z. Returns whether anything was
+    /// reported
+    fn check(&self, expr: &Expression, ast: &mut Vec<AstNode>) -> bool {
+        let mut errors = vec![];
+        let mut children = vec![];
+
+        match expr {
+            Expression::Binary(lhs, Token::Belongs, rhs, _) => {
+                let set = match &**rhs {
+                    Expression::Identifier(set, _) => NumberType::parse(set),
+                    _ => None,
+                };
+
+                if !set.is_some_and(NumberType::is_number_set) {
+                    errors.push(
+                        "Type Error: expected a number set (N, Z, Q, R or C) after `E`".to_string(),
+                    );
+                }
+
+                if let Some(r#type @ (NumberType::Matrix | NumberType::Bool)) = lhs.infer_datatype()
+                {
+                    errors.push(format!(
+                        "Type Error: `E` expects a number on its left, found {type}"
+                    ));
+                }
+
+                children.push(&**lhs);
+            }
+            Expression::Binary(lhs, op, rhs, _) => {
+                let equality = matches!(op, Token::IsEq | Token::NEq);
+
+                match (lhs.infer_datatype(), rhs.infer_datatype()) {
+                    (Some(NumberType::Bool), Some(NumberType::Bool)) if equality => {}
+                    (Some(NumberType::Bool), Some(r#type))
+                    | (Some(r#type), Some(NumberType::Bool))
+                        if equality =>
+                    {
+                        errors.push(format!("Type Error: cannot compare Bool with {type}"))
+                    }
+                    (Some(NumberType::Bool), _) | (_, Some(NumberType::Bool)) if !equality => {
+                        errors.push(format!("Type Error: `{op}` is not defined for Bool"))
+                    }
+                    _ => {}
+                }
+
+                children.extend([&**lhs, &**rhs]);
+            }
+            Expression::Branched(condition, then, otherwise, _) => {
+                if let Some(r#type) = condition.infer_datatype()
+                    && r#type != NumberType::Bool
+                {
+                    errors.push(format!("Type Error: expected Bool condition found {type}"));
+                }
+
+                children.extend([&**condition, &**then, &**otherwise]);
+            }
+            Expression::Integer(n, _) if n.to_i32().is_none() => errors.push(format!(
+                "Type Error: integer literal `{n}` does not fit in Z"
+            )),
+            Expression::Abs(inner, _) => {
+                if inner.infer_datatype() == Some(NumberType::Bool) {
+                    errors.push("Type Error: `|` is not defined for Bool".to_string());
+                }
+
+                children.push(&**inner);
+            }
+            Expression::Matrix(rows, _) => children.extend(rows.iter().flatten()),
+            Expression::FunctionCall(_, args, _) => children.extend(args),
+            _ => {}
+        }
+
+        let mut misused = !errors.is_empty();
+
+        for message in errors {
+            ast.push(AstNode::Error(message, expr.span().clone()));
+        }
+
+        for child in children {
+            misused |= self.check(child, ast);
+        }
+
+        misused
+    }
+
+    /// Left and right binding power of an infix operator, higher binds tighter
+    pub fn infix_binding_power(op: &Token) -> (u16, u16) {
         match op {
-            Token::Add | Token::Sub => (1, 2),
-            Token::Mul | Token::Div | Token::Rem => (3, 4),
-            Token::Pow => (5, 6),
+            // Below arithmetic, `a - 1 > 0` compares `a - 1`
             Token::IsEq
             | Token::NEq
             | Token::Gt
             | Token::Lt
             | Token::GtEq
             | Token::LtEq
-            | Token::Belongs => (7, 8),
+            | Token::Belongs => (1, 2),
+            Token::Add | Token::Sub => (3, 4),
+            Token::Mul | Token::Div | Token::Rem => (5, 6),
+            Token::Pow => (7, 8),
             Token::If | Token::Then | Token::Else | Token::End => (9, 10),
             _ => (0, 0),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ast(source: &str) -> Vec<AstNode> {
+        Parser::new(&SyntaxNode::parse(source)).ast().unwrap()
+    }
+
+    fn errors(ast: &[AstNode]) -> Vec<&str> {
+        ast.iter()
+            .filter_map(|node| match node {
+                AstNode::Error(message, _) => Some(message.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn comparison_binds_looser_than_arithmetic() {
+        let ast = ast("let a = 2\nlet b = a - 1 > 0\nlet c = 2 + 3 > 4");
+
+        assert_eq!(errors(&ast), Vec::<&str>::new());
+
+        let AstNode::Assignment(_, Expression::Binary(lhs, Token::Gt, _, _), ..) = &ast[1] else {
+            panic!("expected a comparison, found {:?}", ast[1]);
+        };
+
+        assert!(matches!(**lhs, Expression::Binary(_, Token::Sub, _, _)));
+    }
+
+    #[test]
+    fn arithmetic_in_condition() {
+        let ast = ast("fn f(n: Z): Z = if n - 1 == 0 then 1 else n * f(n - 1) end");
+
+        assert_eq!(errors(&ast), Vec::<&str>::new());
+
+        let AstNode::FunctionDeclaration(.., Expression::Branched(condition, ..), _, _) = &ast[0]
+        else {
+            panic!("expected a branch, found {:?}", ast[0]);
+        };
+
+        assert!(matches!(
+            &**condition,
+            Expression::Binary(lhs, Token::IsEq, _, _)
+                if matches!(**lhs, Expression::Binary(_, Token::Sub, _, _))
+        ));
+    }
+
+    #[test]
+    fn imaginary_unit_is_not_a_name() {
+        for source in [
+            "let i = 2",
+            "fn i() = 1",
+            "fn f(i) = i + 1",
+            "fn f(x, i: C) = x",
+        ] {
+            assert_eq!(
+                errors(&ast(source)),
+                ["Syntax Error: `i` is the imaginary unit, it can't be a name"],
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn literal_is_not_a_name() {
+        assert_eq!(
+            errors(&ast("let 2 = 3\nfn f(1) = 1")),
+            [
+                "Syntax Error: expected a name found `2`",
+                "Syntax Error: expected a name found `1`"
+            ]
+        );
+    }
+
+    #[test]
+    fn integer_range_is_checked_after_negation() {
+        assert_eq!(errors(&ast("let y = -2147483648")), Vec::<&str>::new());
+        assert_eq!(
+            errors(&ast("let y = 2147483648\nlet z = -2147483649")),
+            [
+                "Type Error: integer literal `2147483648` does not fit in Z",
+                "Type Error: integer literal `-2147483649` does not fit in Z"
+            ]
+        );
+    }
+
+    #[test]
+    fn real_literal_overflow() {
+        for source in ["let z = 1e39", "let z: R = 1e39", "let z = 2e39i"] {
+            let ast = ast(source);
+
+            assert_eq!(errors(&ast), Vec::<&str>::new(), "{source}");
+            assert!(
+                ast.iter().any(|node| matches!(
+                    node,
+                    AstNode::Warning(Lint::Overflow, message, _) if message == "Warning: arithmetic overflow"
+                )),
+                "{source}: {ast:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn bool_operands() {
+        assert_eq!(
+            errors(&ast("let a = (1 > 0) + 1")),
+            ["Type Error: `+` is not defined for Bool"]
+        );
+        assert_eq!(
+            errors(&ast("let a = |2 > 1|")),
+            ["Type Error: `|` is not defined for Bool"]
+        );
+        assert_eq!(
+            errors(&ast("fn f(x: Z) = |x == 1|")),
+            ["Type Error: `|` is not defined for Bool"]
+        );
+        assert_eq!(errors(&ast("let a = |1 - 2| > 0")), Vec::<&str>::new());
+    }
+}
@@ -0,0 +1,76 @@
+use tower_lsp::lsp_types::Position;
+
+use crate::token::{Token, TokenInfo};
+
+/// Maps between LSP positions, whose characters are UTF-16 code units, and lexer
+/// offsets, which count chars from 1 and include one per newline
+pub struct LineIndex<'a> {
+    lines: Vec<&'a str>,
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(file: &'a str) -> Self {
+        let lines = file.lines().collect::<Vec<&str>>();
+        let mut starts = vec![];
+        let mut start = 1;
+
+        for line in &lines {
+            starts.push(start);
+            start += line.chars().count() + 1;
+        }
+
+        Self { lines, starts }
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let line = self
+            .starts
+            .partition_point(|start| *start <= offset)
+            .saturating_sub(1);
+
+        let Some(text) = self.lines.get(line) else {
+            return Position::new(0, 0);
+        };
+
+        let character = text
+            .chars()
+            .take(offset.saturating_sub(self.starts[line]))
+            .map(char::len_utf16)
+            .sum::<usize>();
+
+        Position::new(line as u32, character as u32)
+    }
+
+    pub fn offset(&self, position: Position) -> usize {
+        let line = position.line as usize;
+
+        let Some(text) = self.lines.get(line) else {
+            return self.starts.last().copied().unwrap_or(1)
+                + self.lines.last().map_or(0, |f| f.chars().count());
+        };
+
+        let mut units = 0;
+        let mut chars = 0;
+
+        for char in text.chars() {
+            if units >= position.character as usize {
+                break;
+            }
+
+            units += char.len_utf16();
+            chars += 1;
+        }
+
+        self.starts[line] + chars
+    }
+}
+
+/// The token covering the char at `offset`
+pub fn token_at(tokens: &[TokenInfo], offset: usize) -> Option<&TokenInfo> {
+    tokens.iter().find(|tokeninfo| {
+        tokeninfo.token != Token::Newline
+            && *tokeninfo.range.start() <= offset
+            && offset < *tokeninfo.range.end()
+    })
+}
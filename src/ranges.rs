@@ -0,0 +1,194 @@
+use tower_lsp::lsp_types::{FoldingRange, FoldingRangeKind, Range, SelectionRange};
+
+use crate::{
+    ast::{AstNode, Expression, Span},
+    lexer::Lexer,
+    position::{LineIndex, token_at},
+    syntax::SyntaxKind,
+    token::TokenInfo,
+};
+
+/// The regions of a document that can be folded: matrices and `if` blocks written over
+/// several lines, runs of comment lines and runs of function declarations
+pub fn folding_ranges(text: &str, ast: &[AstNode]) -> Vec<FoldingRange> {
+    let index = LineIndex::new(text);
+    let mut ranges = vec![];
+
+    let mut fold = |start: u32, end: u32, kind: Option<FoldingRangeKind>| {
+        if start < end {
+            ranges.push(FoldingRange {
+                start_line: start,
+                end_line: end,
+                kind,
+                ..Default::default()
+            });
+        }
+    };
+
+    let mut stack = ast
+        .iter()
+        .flat_map(expressions)
+        .collect::<Vec<&Expression>>();
+
+    while let Some(expr) = stack.pop() {
+        if let Expression::Matrix(_, span) | Expression::Branched(.., span) = expr {
+            fold(
+                index.position(*span.start()).line,
+                index.position(*span.end()).line,
+                None,
+            );
+        }
+
+        stack.extend(expr.children());
+    }
+
+    // Errors and warnings sit between the statements they are about, they don't break up
+    // a run of declarations
+    let statements = ast
+        .iter()
+        .filter(|node| !matches!(node, AstNode::Error(..) | AstNode::Warning(..)))
+        .collect::<Vec<&AstNode>>();
+
+    for run in statements.chunk_by(|a, b| {
+        matches!(a, AstNode::FunctionDeclaration(..))
+            && matches!(b, AstNode::FunctionDeclaration(..))
+    }) {
+        if let [first, .., last] = run {
+            fold(
+                index.position(*first.span().start()).line,
+                index.position(*last.span().end()).line,
+                Some(FoldingRangeKind::Region),
+            );
+        }
+    }
+
+    let tokens = Lexer::new(text).lossless_tokens();
+
+    let comments = tokens
+        .split(|token| token.kind() == SyntaxKind::Newline)
+        .map(|line| {
+            let mut kinds = line
+                .iter()
+                .map(|token| token.kind())
+                .filter(|kind| *kind != SyntaxKind::Whitespace)
+                .peekable();
+
+            kinds.peek().is_some()
+                && kinds.all(|kind| matches!(kind, SyntaxKind::Comment | SyntaxKind::DocComment))
+        })
+        .collect::<Vec<bool>>();
+
+    let mut line = 0;
+
+    for run in comments.chunk_by(|a, b| a == b) {
+        if run[0] {
+            fold(
+                line as u32,
+                (line + run.len() - 1) as u32,
+                Some(FoldingRangeKind::Comment),
+            );
+        }
+
+        line += run.len();
+    }
+
+    ranges
+}
+
+/// The selection at `offset` and the ones around it, growing from the token to each
+/// expression holding it, as the parser nested them by precedence, and then to the whole
+/// statement
+pub fn selection_range(
+    text: &str,
+    tokens: &[TokenInfo],
+    ast: &[AstNode],
+    offset: usize,
+) -> SelectionRange {
+    let index = LineIndex::new(text);
+
+    let mut spans = vec![];
+
+    if let Some(tokeninfo) = token_at(tokens, offset) {
+        spans.push(tokeninfo.range.clone());
+    }
+
+    if let Some((statement, exprs)) = enclosing(ast, offset) {
+        spans.extend(exprs.iter().rev().map(|expr| expr.span().clone()));
+        spans.push(ast[statement].span().clone());
+    }
+
+    let mut selection: Option<SelectionRange> = None;
+
+    // Each selection is the parent of the one before, a parent has to hold its child
+    for span in spans.into_iter().rev() {
+        if let Some(parent) = &selection
+            && !contains(&parent.range, &span, &index)
+        {
+            continue;
+        }
+
+        let range = Range::new(index.position(*span.start()), index.position(*span.end()));
+
+        if selection
+            .as_ref()
+            .is_some_and(|parent| parent.range == range)
+        {
+            continue;
+        }
+
+        selection = Some(SelectionRange {
+            range,
+            parent: selection.map(Box::new),
+        });
+    }
+
+    selection.unwrap_or_else(|| {
+        let position = index.position(offset);
+
+        SelectionRange {
+            range: Range::new(position, position),
+            parent: None,
+        }
+    })
+}
+
+/// The statement at `offset`, by index, and its expressions holding the char there,
+/// outermost first as the parser nested them by precedence
+pub fn enclosing(ast: &[AstNode], offset: usize) -> Option<(usize, Vec<&Expression>)> {
+    let statement = ast.iter().position(|node| match node {
+        AstNode::Assignment(.., spans)
+        | AstNode::FunctionCall(.., spans)
+        | AstNode::FunctionDeclaration(.., spans)
+        | AstNode::Use(_, spans) => spans.node.contains(&offset),
+        AstNode::Error(..) | AstNode::Warning(..) => false,
+    })?;
+
+    let mut enclosing = vec![];
+    let mut exprs = expressions(&ast[statement]);
+
+    while let Some(expr) = exprs
+        .iter()
+        .copied()
+        .find(|expr| *expr.span().start() <= offset && offset < *expr.span().end())
+    {
+        enclosing.push(expr);
+        exprs = expr.children();
+    }
+
+    Some((statement, enclosing))
+}
+
+/// The expressions right under a statement
+fn expressions(node: &AstNode) -> Vec<&Expression> {
+    match node {
+        AstNode::Assignment(_, expr, ..) | AstNode::FunctionDeclaration(.., expr, _, _) => {
+            vec![expr]
+        }
+        AstNode::FunctionCall(_, args, _) => args.iter().collect(),
+        AstNode::Use(..) | AstNode::Error(..) | AstNode::Warning(..) => vec![],
+    }
+}
+
+fn contains(range: &Range, span: &Span, index: &LineIndex) -> bool {
+    range.start <= index.position(*span.start()) && index.position(*span.end()) <= range.end
+}
@@ -0,0 +1,202 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{AstNode, Expression, Lint, Span};
+
+/// How the functions declared in a file call each other. Calls go to the first declaration
+/// of a name, and an imported function can't call back into the file
+#[derive(Debug, Default)]
+pub struct Recursion {
+    /// The functions that call themselves, with the other functions they do it through in
+    /// declaration order, none for a function calling itself directly
+    pub recursive: HashMap<String, Vec<String>>,
+    /// Recursion that never ends
+    pub warnings: Vec<AstNode>,
+}
+
+struct Function<'a> {
+    name: &'a str,
+    params: Vec<&'a str>,
+    body: &'a Expression,
+    span: &'a Span,
+}
+
+impl Recursion {
+    pub fn new(ast: &[AstNode]) -> Self {
+        let mut functions = vec![];
+
+        for node in ast {
+            if let AstNode::FunctionDeclaration(name, args, _, body, _, spans) = node
+                && !functions
+                    .iter()
+                    .any(|function: &Function| function.name == name)
+            {
+                functions.push(Function {
+                    name,
+                    params: args.iter().map(|(arg, _)| arg.as_str()).collect(),
+                    body,
+                    span: &spans.name,
+                });
+            }
+        }
+
+        let index = |name: &str| functions.iter().position(|function| function.name == name);
+
+        // The calls in each body to functions of the file, by index
+        let calls = functions
+            .iter()
+            .map(|function| {
+                function
+                    .body
+                    .calls()
+                    .into_iter()
+                    .filter_map(|(name, args, span)| Some((index(name)?, args, span)))
+                    .collect()
+            })
+            .collect::<Vec<Vec<(usize, &[Expression], Span)>>>();
+
+        let reach = (0..functions.len())
+            .map(|i| reachable(i, |j| calls[j].iter().map(|(k, ..)| *k)))
+            .collect::<Vec<HashSet<usize>>>();
+
+        // The functions each function calls and is called back by, itself included when
+        // it is recursive
+        let groups = (0..functions.len())
+            .map(|i| {
+                (0..functions.len())
+                    .filter(|j| reach[i].contains(j) && reach[*j].contains(&i))
+                    .collect::<Vec<usize>>()
+            })
+            .collect::<Vec<Vec<usize>>>();
+
+        let mut recursion = Self::default();
+
+        for (i, group) in groups.iter().enumerate() {
+            if group.contains(&i) {
+                recursion.recursive.insert(
+                    functions[i].name.to_string(),
+                    group
+                        .iter()
+                        .filter(|j| **j != i)
+                        .map(|j| functions[*j].name.to_string())
+                        .collect(),
+                );
+            }
+        }
+
+        // A function returns when some branch of its body only calls functions that
+        // return. Functions outside its own recursion are reported on their own
+        let mut returns = vec![false; functions.len()];
+
+        loop {
+            let mut changed = false;
+
+            for i in 0..functions.len() {
+                if !returns[i]
+                    && finishes(functions[i].body, &|name| {
+                        index(name).is_none_or(|j| returns[j] || !groups[i].contains(&j))
+                    })
+                {
+                    returns[i] = true;
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        for (i, function) in functions.iter().enumerate() {
+            if groups[i].contains(&i) && !returns[i] {
+                recursion.warnings.push(AstNode::Warning(
+                    Lint::Recursion,
+                    format!(
+                        "Warning: `{}` has no base case, every branch of it recurses",
+                        function.name
+                    ),
+                    function.span.clone(),
+                ));
+            }
+        }
+
+        // A call passing on the parameters it was given, in order, runs the same as the
+        // call it is in. Going around a cycle of those never ends
+        let unchanged = functions
+            .iter()
+            .zip(&calls)
+            .map(|(function, calls)| {
+                calls
+                    .iter()
+                    .filter(|(_, args, _)| {
+                        args.len() == function.params.len()
+                            && args.iter().zip(&function.params).all(|(arg, param)| {
+                                matches!(arg, Expression::Identifier(name, _) if name == param)
+                            })
+                    })
+                    .map(|(j, _, span)| (*j, span))
+                    .collect()
+            })
+            .collect::<Vec<Vec<(usize, &Span)>>>();
+
+        for (i, calls) in unchanged.iter().enumerate() {
+            for (j, span) in calls {
+                let cycle =
+                    *j == i || reachable(*j, |k| unchanged[k].iter().map(|(l, _)| *l)).contains(&i);
+
+                if cycle {
+                    recursion.warnings.push(AstNode::Warning(
+                        Lint::Recursion,
+                        format!(
+                            "Warning: `{}` is called again with the same arguments, the recursion never ends",
+                            functions[*j].name
+                        ),
+                        (*span).clone(),
+                    ));
+                }
+            }
+        }
+
+        recursion
+    }
+}
+
+/// Whether evaluating `expr` can produce a value, `returns` telling which called functions
+/// do. Only an `if` lets part of an expression go unevaluated
+fn finishes(expr: &Expression, returns: &impl Fn(&str) -> bool) -> bool {
+    match expr {
+        Expression::Branched(condition, then, otherwise, _) => {
+            finishes(condition, returns)
+                && (finishes(then, returns) || finishes(otherwise, returns))
+        }
+        Expression::FunctionCall(name, args, _) => {
+            returns(name) && args.iter().all(|arg| finishes(arg, returns))
+        }
+        Expression::Abs(expr, _) => finishes(expr, returns),
+        Expression::Binary(lhs, _, rhs, _) => finishes(lhs, returns) && finishes(rhs, returns),
+        Expression::Matrix(rows, _) => rows.iter().flatten().all(|expr| finishes(expr, returns)),
+        Expression::Identifier(..)
+        | Expression::Integer(..)
+        | Expression::Real(..)
+        | Expression::Imaginary(..)
+        | Expression::Error(_) => true,
+    }
+}
+
+/// The nodes reachable from `start` in one step or more
+fn reachable<I: IntoIterator<Item = usize>>(
+    start: usize,
+    next: impl Fn(usize) -> I,
+) -> HashSet<usize> {
+    let mut seen = HashSet::new();
+    let mut stack = vec![start];
+
+    while let Some(node) = stack.pop() {
+        for next in next(node) {
+            if seen.insert(next) {
+                stack.push(next);
+            }
+        }
+    }
+
+    seen
+}
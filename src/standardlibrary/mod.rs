@@ -1,6 +1,5 @@
 use crate::types::NumberType;
 
-// TODO: Add operands in this
 /// Returns argument type and return type
 pub fn internal_type_map(f: &str) -> (Vec<Vec<NumberType>>, NumberType) {
     match f {
@@ -13,9 +12,27 @@ pub fn internal_type_map(f: &str) -> (Vec<Vec<NumberType>>, NumberType) {
             vec![vec![NumberType::Real], vec![NumberType::Real]],
             NumberType::Real,
         ),
-        "transpose" | "determinant" | "adj" | "inverse" => {
-            (vec![vec![NumberType::Matrix]], NumberType::Matrix)
+        "determinant" => (vec![vec![NumberType::Matrix]], NumberType::Real),
+        "transpose" | "adj" | "inverse" => (vec![vec![NumberType::Matrix]], NumberType::Matrix),
+        "add" | "sub" | "mul" | "div" | "pow" | "rem" => {
+            let operand = vec![
+                NumberType::Int,
+                NumberType::Real,
+                NumberType::Complex,
+                NumberType::Matrix,
+            ];
+
+            (vec![operand.clone(), operand], NumberType::Unknown)
         }
+        "is_eq" | "neq" | "gt" | "gteq" | "lt" | "lteq" => (
+            vec![
+                vec![NumberType::Int, NumberType::Real],
+                vec![NumberType::Int, NumberType::Real],
+            ],
+            NumberType::Bool,
+        ),
+        "re" | "im" | "arg" => (vec![vec![NumberType::Complex]], NumberType::Real),
+        "conj" => (vec![vec![NumberType::Complex]], NumberType::Complex),
         "abs" => (
             vec![vec![
                 NumberType::Int,
@@ -29,7 +46,7 @@ pub fn internal_type_map(f: &str) -> (Vec<Vec<NumberType>>, NumberType) {
     }
 }
 
-pub const STD: [&str; 34] = [
+pub const STD: [&str; 38] = [
     "print",
     "read",
     "int",
@@ -64,4 +81,8 @@ pub const STD: [&str; 34] = [
     "determinant",
     "adj",
     "inverse",
+    "re",
+    "im",
+    "conj",
+    "arg",
 ];
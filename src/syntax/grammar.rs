@@ -0,0 +1,451 @@
+use std::sync::Arc;
+
+use super::{GreenElement, GreenNode, GreenToken, SyntaxKind};
+use crate::{parser::Parser, token::Token};
+
+/// Builds the green tree of a document from its lossless tokens. Statements are split the
+/// same way [`Parser`] splits them, then each one is parsed with the same binding powers.
+/// Anything the grammar doesn't expect is wrapped in an `Error` node instead of dropped
+pub fn parse(tokens: Vec<GreenToken>) -> GreenNode {
+    let statements = statements(&tokens);
+
+    let mut grammar = Grammar {
+        tokens,
+        position: 0,
+        end: 0,
+        stack: vec![(SyntaxKind::Root, vec![])],
+    };
+
+    for (start, end) in statements {
+        grammar.trivia_until(start);
+        grammar.end = end + 1;
+
+        // A doc comment on its own documents the next declaration
+        if grammar.tokens[start].kind() == SyntaxKind::DocComment {
+            grammar.trivia_until(start + 1);
+            continue;
+        }
+
+        grammar.statement();
+    }
+
+    grammar.end = grammar.tokens.len();
+    grammar.trivia_until(grammar.tokens.len());
+
+    let (kind, children) = grammar.stack.pop().unwrap();
+
+    GreenNode::new(kind, children)
+}
+
+/// First and last token of each statement. A newline only ends a statement once every
+/// bracket and `if` block opened on it is closed, or when the next line starts a new
+/// declaration
+fn statements(tokens: &[GreenToken]) -> Vec<(usize, usize)> {
+    let mut statements = vec![];
+    let mut statement: Option<(usize, usize)> = None;
+    let mut depth = 0usize;
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token.kind() {
+            SyntaxKind::Whitespace | SyntaxKind::Comment => continue,
+            SyntaxKind::LParen | SyntaxKind::LSquare | SyntaxKind::LCurly | SyntaxKind::If => {
+                depth += 1
+            }
+            SyntaxKind::RParen | SyntaxKind::RSquare | SyntaxKind::RCurly | SyntaxKind::End => {
+                depth = depth.saturating_sub(1)
+            }
+            // Doc comments inside an open statement don't document anything, they are
+            // trivia of the statement
+            SyntaxKind::DocComment if depth > 0 => {}
+            SyntaxKind::Newline => {
+                let declaration = tokens[i + 1..]
+                    .iter()
+                    .find(|t| !matches!(t.kind(), SyntaxKind::Whitespace | SyntaxKind::Comment))
+                    .is_some_and(|t| {
+                        matches!(t.kind(), SyntaxKind::Let | SyntaxKind::Fn | SyntaxKind::Use)
+                    });
+
+                if (depth == 0 || declaration)
+                    && let Some(statement) = statement.take()
+                {
+                    statements.push(statement);
+                    depth = 0;
+                }
+
+                continue;
+            }
+            _ => {}
+        }
+
+        statement = Some(statement.map_or((i, i), |(start, _)| (start, i)));
+    }
+
+    statements.extend(statement);
+
+    statements
+}
+
+struct Grammar {
+    tokens: Vec<GreenToken>,
+    position: usize,
+    /// One past the last token of the statement being parsed
+    end: usize,
+    stack: Vec<(SyntaxKind, Vec<GreenElement>)>,
+}
+
+impl Grammar {
+    /// Opens a node at the next token, the trivia before it stays outside
+    fn start_node(&mut self, kind: SyntaxKind) {
+        self.trivia_until(self.next().unwrap_or(self.position));
+        self.stack.push((kind, vec![]));
+    }
+
+    fn finish_node(&mut self) {
+        let (kind, children) = self.stack.pop().unwrap();
+
+        self.push(GreenElement::Node(Arc::new(GreenNode::new(kind, children))));
+    }
+
+    /// Where a node that wraps what comes next could start, used for binary
+    /// expressions which only know they are one after their left operand
+    fn checkpoint(&mut self) -> usize {
+        self.trivia_until(self.next().unwrap_or(self.position));
+
+        self.stack.last().unwrap().1.len()
+    }
+
+    fn start_node_at(&mut self, checkpoint: usize, kind: SyntaxKind) {
+        let children = self.stack.last_mut().unwrap().1.split_off(checkpoint);
+
+        self.stack.push((kind, children));
+    }
+
+    fn push(&mut self, element: GreenElement) {
+        self.stack.last_mut().unwrap().1.push(element);
+    }
+
+    fn trivia_until(&mut self, end: usize) {
+        while self.position < end {
+            let token = self.tokens[self.position].clone();
+
+            self.push(GreenElement::Token(Arc::new(token)));
+            self.position += 1;
+        }
+    }
+
+    /// Index of the next token that isn't trivia within the statement
+    fn next(&self) -> Option<usize> {
+        (self.position..self.end).find(|i| !self.tokens[*i].kind().is_trivia())
+    }
+
+    fn peek(&self) -> Option<SyntaxKind> {
+        self.next().map(|i| self.tokens[i].kind())
+    }
+
+    fn nth(&self, n: usize) -> Option<SyntaxKind> {
+        (self.position..self.end)
+            .filter(|i| !self.tokens[*i].kind().is_trivia())
+            .nth(n)
+            .map(|i| self.tokens[i].kind())
+    }
+
+    fn bump(&mut self) {
+        if let Some(next) = self.next() {
+            self.trivia_until(next + 1);
+        }
+    }
+
+    fn bump_as(&mut self, kind: SyntaxKind) {
+        self.start_node(kind);
+        self.bump();
+        self.finish_node();
+    }
+
+    fn statement(&mut self) {
+        match self.peek() {
+            Some(SyntaxKind::Let) => {
+                self.start_node(SyntaxKind::LetStatement);
+                self.bump();
+                self.name();
+                self.annotation();
+
+                if self.peek() == Some(SyntaxKind::Eq) {
+                    self.bump();
+                    self.expression(0);
+                }
+            }
+            Some(SyntaxKind::Fn) => {
+                self.start_node(SyntaxKind::FnStatement);
+                self.bump();
+                self.name();
+
+                if self.peek() == Some(SyntaxKind::LParen) {
+                    self.params();
+                }
+
+                self.annotation();
+
+                if self.peek() == Some(SyntaxKind::Eq) {
+                    self.bump();
+                    self.expression(0);
+                }
+            }
+            Some(SyntaxKind::Use) => {
+                self.start_node(SyntaxKind::UseStatement);
+                self.bump();
+
+                if self.peek() == Some(SyntaxKind::Str) {
+                    self.bump();
+                }
+            }
+            _ => {
+                self.start_node(SyntaxKind::ExpressionStatement);
+                self.expression(0);
+            }
+        }
+
+        if self.peek().is_some() {
+            self.start_node(SyntaxKind::Error);
+
+            while self.peek().is_some() {
+                self.bump();
+            }
+
+            self.finish_node();
+        }
+
+        self.trivia_until(self.end);
+        self.finish_node();
+    }
+
+    fn name(&mut self) {
+        if self.peek() == Some(SyntaxKind::Identifier) {
+            self.bump_as(SyntaxKind::Name);
+        }
+    }
+
+    fn annotation(&mut self) {
+        if self.peek() != Some(SyntaxKind::Colon) {
+            return;
+        }
+
+        self.start_node(SyntaxKind::TypeAnnotation);
+        self.bump();
+
+        if self.peek() == Some(SyntaxKind::Identifier) {
+            self.bump();
+        }
+
+        self.finish_node();
+    }
+
+    fn params(&mut self) {
+        self.start_node(SyntaxKind::ParamList);
+        self.bump();
+
+        loop {
+            match self.peek() {
+                None => break,
+                Some(SyntaxKind::RParen) => {
+                    self.bump();
+                    break;
+                }
+                Some(SyntaxKind::Comma) => self.bump(),
+                Some(SyntaxKind::Identifier) => {
+                    self.start_node(SyntaxKind::Param);
+                    self.name();
+                    self.annotation();
+                    self.finish_node();
+                }
+                Some(_) => self.bump_as(SyntaxKind::Error),
+            }
+        }
+
+        self.finish_node();
+    }
+
+    /// Binding powers of the operator after the cursor. `2x` multiplies without an
+    /// operator token, like the `*` the lexer implies there
+    fn infix(&self) -> Option<(u16, u16)> {
+        let next = self.next()?;
+        let kind = self.tokens[next].kind();
+
+        if matches!(kind, SyntaxKind::Identifier | SyntaxKind::Imaginary) {
+            let previous = (0..self.position)
+                .rev()
+                .find(|i| !self.tokens[*i].kind().is_trivia())?;
+
+            let implied = matches!(
+                self.tokens[previous].kind(),
+                SyntaxKind::Integer | SyntaxKind::Float
+            ) && !self.tokens[previous..next]
+                .iter()
+                .any(|t| t.kind() == SyntaxKind::Newline);
+
+            return implied.then(|| Parser::infix_binding_power(&Token::Mul));
+        }
+
+        if matches!(
+            kind,
+            SyntaxKind::If | SyntaxKind::Then | SyntaxKind::Else | SyntaxKind::End
+        ) {
+            return None;
+        }
+
+        let (SyntaxKind::Add
+        | SyntaxKind::Sub
+        | SyntaxKind::Mul
+        | SyntaxKind::Div
+        | SyntaxKind::Pow
+        | SyntaxKind::Rem
+        | SyntaxKind::IsEq
+        | SyntaxKind::NEq
+        | SyntaxKind::Gt
+        | SyntaxKind::Lt
+        | SyntaxKind::GtEq
+        | SyntaxKind::LtEq
+        | SyntaxKind::Belongs) = kind
+        else {
+            return None;
+        };
+
+        let token = Token::new(self.tokens[next].text.clone());
+
+        Some(Parser::infix_binding_power(&token))
+    }
+
+    fn expression(&mut self, prec: u16) {
+        let checkpoint = self.checkpoint();
+
+        if !self.operand() {
+            return;
+        }
+
+        while let Some((lbp, rbp)) = self.infix() {
+            if lbp < prec {
+                break;
+            }
+
+            self.start_node_at(checkpoint, SyntaxKind::BinaryExpression);
+
+            if !matches!(
+                self.peek(),
+                Some(SyntaxKind::Identifier | SyntaxKind::Imaginary)
+            ) {
+                self.bump();
+            }
+
+            self.expression(rbp);
+            self.finish_node();
+        }
+    }
+
+    /// Parses what can stand on either side of an operator, returns whether there was
+    /// anything to parse
+    fn operand(&mut self) -> bool {
+        match self.peek() {
+            Some(SyntaxKind::Integer | SyntaxKind::Float | SyntaxKind::Imaginary) => {
+                self.bump_as(SyntaxKind::Literal);
+            }
+            Some(SyntaxKind::Sub) => {
+                self.start_node(SyntaxKind::PrefixExpression);
+                self.bump();
+                self.operand();
+                self.finish_node();
+            }
+            Some(SyntaxKind::Identifier) if self.nth(1) == Some(SyntaxKind::LParen) => {
+                self.start_node(SyntaxKind::CallExpression);
+                self.bump_as(SyntaxKind::NameRef);
+                self.start_node(SyntaxKind::ArgList);
+                self.bump();
+                self.list(SyntaxKind::RParen);
+                self.finish_node();
+                self.finish_node();
+            }
+            Some(SyntaxKind::Identifier) => self.bump_as(SyntaxKind::NameRef),
+            Some(SyntaxKind::LParen) => {
+                self.delimited(SyntaxKind::ParenExpression, SyntaxKind::RParen)
+            }
+            Some(SyntaxKind::Abs) => self.delimited(SyntaxKind::AbsExpression, SyntaxKind::Abs),
+            Some(SyntaxKind::LSquare) => {
+                self.start_node(SyntaxKind::MatrixExpression);
+                self.bump();
+
+                loop {
+                    self.start_node(SyntaxKind::Row);
+                    self.list(SyntaxKind::SemiColon);
+                    self.finish_node();
+
+                    match self.peek() {
+                        Some(SyntaxKind::SemiColon) => self.bump(),
+                        Some(SyntaxKind::RSquare) => {
+                            self.bump();
+                            break;
+                        }
+                        _ => break,
+                    }
+                }
+
+                self.finish_node();
+            }
+            Some(SyntaxKind::If) => {
+                self.start_node(SyntaxKind::IfExpression);
+                self.bump();
+                self.expression(0);
+
+                for keyword in [SyntaxKind::Then, SyntaxKind::Else] {
+                    if self.peek() == Some(keyword) {
+                        self.bump();
+                        self.expression(0);
+                    }
+                }
+
+                if self.peek() == Some(SyntaxKind::End) {
+                    self.bump();
+                }
+
+                self.finish_node();
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn delimited(&mut self, kind: SyntaxKind, close: SyntaxKind) {
+        self.start_node(kind);
+        self.bump();
+        self.expression(0);
+
+        if self.peek() == Some(close) {
+            self.bump();
+        }
+
+        self.finish_node();
+    }
+
+    /// Comma separated expressions up to `close`, or the `]` of a matrix. The closing
+    /// token is left for the caller
+    fn list(&mut self, close: SyntaxKind) {
+        loop {
+            match self.peek() {
+                None => break,
+                Some(kind) if kind == close || kind == SyntaxKind::RSquare => break,
+                Some(SyntaxKind::Comma) => self.bump(),
+                Some(_) => {
+                    let next = self.next();
+
+                    self.expression(0);
+
+                    if self.next() == next {
+                        self.bump_as(SyntaxKind::Error);
+                    }
+                }
+            }
+        }
+
+        if close == SyntaxKind::RParen && self.peek() == Some(close) {
+            self.bump();
+        }
+    }
+}
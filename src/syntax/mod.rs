@@ -0,0 +1,414 @@
+mod grammar;
+
+use std::sync::Arc;
+
+use crate::{
+    ast::Span,
+    lexer::Lexer,
+    token::{Token, TokenInfo},
+};
+
+/// Kinds of the tokens and nodes of the concrete syntax tree. Token kinds mirror
+/// [`Token`], plus the trivia the lexer otherwise skips
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    Float,
+    Integer,
+    Imaginary,
+    Identifier,
+    Str,
+    LexError,
+
+    Let,
+    Fn,
+    Use,
+    If,
+    Then,
+    Else,
+    End,
+
+    Eq,
+    NEq,
+    IsEq,
+    Gt,
+    Lt,
+    GtEq,
+    LtEq,
+
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Rem,
+
+    Comma,
+    Belongs,
+    Colon,
+    SemiColon,
+    LParen,
+    RParen,
+    LSquare,
+    RSquare,
+    LCurly,
+    RCurly,
+    Abs,
+    Newline,
+    DocComment,
+
+    Whitespace,
+    Comment,
+
+    Root,
+    LetStatement,
+    FnStatement,
+    UseStatement,
+    ExpressionStatement,
+    Name,
+    TypeAnnotation,
+    ParamList,
+    Param,
+    Literal,
+    NameRef,
+    PrefixExpression,
+    BinaryExpression,
+    ParenExpression,
+    AbsExpression,
+    MatrixExpression,
+    Row,
+    IfExpression,
+    CallExpression,
+    ArgList,
+    Error,
+}
+
+impl SyntaxKind {
+    /// Tokens the grammar skips over, they are kept in the tree but carry no meaning
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::Whitespace
+                | SyntaxKind::Comment
+                | SyntaxKind::Newline
+                | SyntaxKind::DocComment
+        )
+    }
+}
+
+impl From<&Token> for SyntaxKind {
+    fn from(token: &Token) -> Self {
+        match token {
+            Token::Float(_) => SyntaxKind::Float,
+            Token::Integer(_) => SyntaxKind::Integer,
+            Token::Imaginary(_) => SyntaxKind::Imaginary,
+            Token::Identifier(_) => SyntaxKind::Identifier,
+            Token::Str(_) => SyntaxKind::Str,
+            Token::Error(_) => SyntaxKind::LexError,
+            Token::Let => SyntaxKind::Let,
+            Token::Fn => SyntaxKind::Fn,
+            Token::Use => SyntaxKind::Use,
+            Token::If => SyntaxKind::If,
+            Token::Then => SyntaxKind::Then,
+            Token::Else => SyntaxKind::Else,
+            Token::End => SyntaxKind::End,
+            Token::Eq => SyntaxKind::Eq,
+            Token::NEq => SyntaxKind::NEq,
+            Token::IsEq => SyntaxKind::IsEq,
+            Token::Gt => SyntaxKind::Gt,
+            Token::Lt => SyntaxKind::Lt,
+            Token::GtEq => SyntaxKind::GtEq,
+            Token::LtEq => SyntaxKind::LtEq,
+            Token::Add => SyntaxKind::Add,
+            Token::Sub => SyntaxKind::Sub,
+            Token::Mul => SyntaxKind::Mul,
+            Token::Div => SyntaxKind::Div,
+            Token::Pow => SyntaxKind::Pow,
+            Token::Rem => SyntaxKind::Rem,
+            Token::Comma => SyntaxKind::Comma,
+            Token::Belongs => SyntaxKind::Belongs,
+            Token::Colon => SyntaxKind::Colon,
+            Token::SemiColon => SyntaxKind::SemiColon,
+            Token::LParen => SyntaxKind::LParen,
+            Token::RParen => SyntaxKind::RParen,
+            Token::LSquare => SyntaxKind::LSquare,
+            Token::RSquare => SyntaxKind::RSquare,
+            Token::LCurly => SyntaxKind::LCurly,
+            Token::RCurly => SyntaxKind::RCurly,
+            Token::Abs => SyntaxKind::Abs,
+            Token::Newline => SyntaxKind::Newline,
+            Token::DocComment(_) => SyntaxKind::DocComment,
+        }
+    }
+}
+
+/// A token of the green tree, the source text it covers and nothing else so that
+/// identical tokens can be shared
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenToken {
+    kind: SyntaxKind,
+    text: String,
+}
+
+impl GreenToken {
+    pub fn new(kind: SyntaxKind, text: String) -> Self {
+        Self { kind, text }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Width in lexer offsets, a line terminator counts once even when it is `\r\n`
+    fn len(&self) -> usize {
+        match self.kind {
+            SyntaxKind::Newline => 1,
+            _ => self.text.chars().count(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(Arc<GreenToken>),
+}
+
+impl GreenElement {
+    fn len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.len,
+            GreenElement::Token(token) => token.len(),
+        }
+    }
+}
+
+/// A node of the green tree, it knows its width but not where it is, so an unchanged
+/// subtree can be reused anywhere in the document
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    len: usize,
+    children: Vec<GreenElement>,
+}
+
+impl GreenNode {
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> Self {
+        Self {
+            kind,
+            len: children.iter().map(GreenElement::len).sum(),
+            children,
+        }
+    }
+
+    fn write(&self, text: &mut String) {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => node.write(text),
+                GreenElement::Token(token) => text.push_str(&token.text),
+            }
+        }
+    }
+}
+
+/// A node of the red tree, a green node placed at its lexer offset in the document
+#[derive(Debug, Clone)]
+pub struct SyntaxNode {
+    green: Arc<GreenNode>,
+    offset: usize,
+}
+
+/// A token of the red tree, a green token placed at its lexer offset in the document
+#[derive(Debug, Clone)]
+pub struct SyntaxToken {
+    green: Arc<GreenToken>,
+    offset: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    /// Builds the lossless tree of a document, whitespace, comments and invalid tokens
+    /// included
+    pub fn parse(source: &str) -> Self {
+        let root = Self {
+            green: Arc::new(grammar::parse(Lexer::new(source).lossless_tokens())),
+            offset: 1,
+        };
+
+        debug_assert_eq!(root.text(), source, "the syntax tree must be lossless");
+
+        root
+    }
+
+    /// The source covered by the node, byte for byte
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+
+        self.green.write(&mut text);
+
+        text
+    }
+
+    pub fn children_with_tokens(&self) -> impl Iterator<Item = SyntaxElement> + '_ {
+        self.green
+            .children
+            .iter()
+            .scan(self.offset, |offset, child| {
+                let start = *offset;
+                *offset += child.len();
+
+                Some(match child {
+                    GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode {
+                        green: green.clone(),
+                        offset: start,
+                    }),
+                    GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                        green: green.clone(),
+                        offset: start,
+                    }),
+                })
+            })
+    }
+
+    /// Every token under the node, in source order
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        self.children_with_tokens()
+            .flat_map(|child| match child {
+                SyntaxElement::Node(node) => node.tokens(),
+                SyntaxElement::Token(token) => vec![token],
+            })
+            .collect()
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind
+    }
+
+    pub fn range(&self) -> Span {
+        self.offset..=self.offset + self.green.len()
+    }
+
+    /// The token the lexer produces for this piece of source
+    pub fn info(&self) -> TokenInfo {
+        let text = &self.green.text;
+
+        let token = match self.kind() {
+            SyntaxKind::Newline => Token::Newline,
+            SyntaxKind::DocComment => {
+                let comment = &text[3..];
+
+                Token::DocComment(comment.strip_prefix(' ').unwrap_or(comment).to_string())
+            }
+            _ => Token::new(text.to_string()),
+        };
+
+        TokenInfo::new(token, self.range())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `source`, checking that the tree and its tokens give it back byte for byte
+    /// and that the tokens follow each other without gaps
+    fn round_trip(source: &str) -> SyntaxNode {
+        let root = SyntaxNode::parse(source);
+
+        assert_eq!(root.text(), source);
+
+        let tokens = root.tokens();
+
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|token| token.green.text())
+                .collect::<String>(),
+            source
+        );
+
+        let mut offset = 1;
+
+        for token in &tokens {
+            assert_eq!(*token.range().start(), offset, "{token:?} in {source:?}");
+            offset = *token.range().end();
+        }
+
+        // A `\r\n` is a single offset, like in the lexer
+        assert_eq!(
+            offset,
+            1 + source.chars().count() - source.matches("\r\n").count()
+        );
+
+        root
+    }
+
+    #[test]
+    fn empty() {
+        round_trip("");
+        round_trip("\n\n");
+    }
+
+    #[test]
+    fn comments() {
+        round_trip("// a comment\nlet x = 1 // after\n/* a block\n   over lines */ let y = 2\n");
+        round_trip("let x = /* inline */ 1\n/* unterminated");
+    }
+
+    #[test]
+    fn doc_comments() {
+        let root = round_trip("/// The identity\n/// of `x`\nfn id(x) = x\n//// not a doc\n");
+
+        assert_eq!(
+            root.tokens()
+                .iter()
+                .filter(|token| token.kind() == SyntaxKind::DocComment)
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn crlf() {
+        round_trip("let x = 1\r\nlet y = x\r\n\r\nfn f(a) = a\r\n");
+        round_trip("/// doc\r\nlet x = 1 // comment\r\n");
+    }
+
+    #[test]
+    fn trailing_whitespace() {
+        round_trip("let x = 1   \n\t\nlet y = 2\t \n  ");
+        round_trip("   let x = 1");
+    }
+
+    #[test]
+    fn unicode() {
+        round_trip("let π2 = π * 2\nlet ñ = 1 // café ☕\n/* 日本 */ let z = ñ\n");
+    }
+
+    #[test]
+    fn implied_multiplication_is_not_written() {
+        round_trip("let x = 2\nlet y = 2x + 2.5x\n");
+    }
+
+    #[test]
+    fn lex_errors() {
+        let root =
+            round_trip("let s = \"unterminated\nlet x = 1e99999 + 0x\nlet y = $ @ # 2\nfn (\n");
+
+        assert!(
+            root.tokens()
+                .iter()
+                .any(|token| token.kind() == SyntaxKind::LexError)
+        );
+    }
+}
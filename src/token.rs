@@ -1,6 +1,8 @@
 use std::{fmt::Display, ops::RangeInclusive};
 
-#[derive(Debug, Clone)]
+use crate::number::Literal;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct TokenInfo {
     pub token: Token,
     pub range: RangeInclusive<usize>,
@@ -10,16 +12,27 @@ impl TokenInfo {
     pub fn new(token: Token, range: RangeInclusive<usize>) -> Self {
         Self { token, range }
     }
+
+    /// Moves the token by `by` offsets
+    pub fn shift(&mut self, by: isize) {
+        self.range = self.range.start().saturating_add_signed(by)
+            ..=self.range.end().saturating_add_signed(by);
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum Token {
-    Float(f32),
-    Integer(i32),
+    Float(Literal),
+    Integer(Literal),
+    Imaginary(Literal),
     Identifier(String),
+    /// A string in double quotes, only used for the path of a `use`
+    Str(String),
+    Error(String),
 
     Let,
     Fn,
+    Use,
     If,
     Then,
     Else,
@@ -52,6 +65,8 @@ pub enum Token {
     LCurly,
     RCurly,
     Abs,
+    Newline,
+    DocComment(String),
 }
 
 impl Token {
@@ -59,10 +74,12 @@ impl Token {
         match token.as_str().trim() {
             "let" => Token::Let,
             "fn" => Token::Fn,
+            "use" => Token::Use,
             "if" => Token::If,
             "then" => Token::Then,
             "else" => Token::Else,
             "end" => Token::End,
+            "i" => Token::Imaginary(Literal::imaginary("i").unwrap()),
 
             "=" => Token::Eq,
             "!=" => Token::NEq,
@@ -91,16 +108,23 @@ impl Token {
             "{" => Token::LCurly,
             "}" => Token::RCurly,
             _ => {
-                if token.chars().all(|a| a.is_ascii_digit() || a == '.') {
-                    let try_integer = token.parse::<i32>();
-                    let try_float = token.parse::<f32>();
-
-                    if let Ok(n) = try_integer {
-                        Token::Integer(n)
-                    } else if let Ok(f) = try_float {
-                        Token::Float(f)
-                    } else {
-                        panic!("could not lex number: `{}`", token);
+                if let Some(string) = token.strip_prefix('"') {
+                    match string.strip_suffix('"') {
+                        Some(string) => Token::Str(string.to_string()),
+                        None => Token::Error("Lex Error: unterminated string".to_string()),
+                    }
+                } else if token.starts_with(|a: char| a.is_ascii_digit()) && token.ends_with('i') {
+                    match Literal::imaginary(&token) {
+                        Ok(literal) => Token::Imaginary(literal),
+                        Err(err) => Token::Error(format!("Lex Error: {err}")),
+                    }
+                } else if token.starts_with(|a: char| a.is_ascii_digit()) {
+                    // Whether an integer fits in Z is checked by the parser, once a `-`
+                    // before it is applied
+                    match Literal::parse(&token) {
+                        Ok(literal) if literal.is_real() => Token::Float(literal),
+                        Ok(literal) => Token::Integer(literal),
+                        Err(err) => Token::Error(format!("Lex Error: {err}")),
                     }
                 } else {
                     Token::Identifier(token)
@@ -109,8 +133,22 @@ impl Token {
         }
     }
 
+    /// Whether the token is an operator producing a truth value
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Token::IsEq
+                | Token::NEq
+                | Token::Gt
+                | Token::Lt
+                | Token::GtEq
+                | Token::LtEq
+                | Token::Belongs
+        )
+    }
+
     pub fn dictionary() -> Vec<String> {
-        ["let", "fn", "if", "then", "else", "end"]
+        ["let", "fn", "use", "if", "then", "else", "end"]
             .iter()
             .map(|f| f.to_string())
             .collect::<Vec<String>>()
@@ -125,9 +163,13 @@ impl Display for Token {
             match self {
                 Token::Integer(n) => n.to_string(),
                 Token::Float(n) => n.to_string(),
+                Token::Imaginary(n) => n.to_string(),
                 Token::Identifier(ident) => ident.to_string(),
+                Token::Str(string) => format!("\"{string}\""),
+                Token::Error(message) => message.to_string(),
                 Token::Let => "let".to_string(),
                 Token::Fn => "fn".to_string(),
+                Token::Use => "use".to_string(),
                 Token::If => "if".to_string(),
                 Token::Then => "then".to_string(),
                 Token::Else => "else".to_string(),
@@ -156,6 +198,8 @@ impl Display for Token {
                 Token::RSquare => "]".to_string(),
                 Token::LCurly => "{".to_string(),
                 Token::RCurly => "}".to_string(),
+                Token::Newline => "\n".to_string(),
+                Token::DocComment(comment) => format!("/// {comment}"),
             }
         )
     }
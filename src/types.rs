@@ -2,23 +2,59 @@ use std::fmt::Display;
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Ord, Eq)]
 pub enum NumberType {
+	Natural,
 	Int,
+	Rational,
 	Real,
 	Complex,
 	Matrix,
+	Bool,
 	Unknown,
 }
 
 impl NumberType {
-	pub fn parse(ident: &str) -> Self {
+	pub fn parse(ident: &str) -> Option<Self> {
 		match ident.to_uppercase().as_str() {
-			"Z" | "INT" | "INTEGER" => Self::Int,
-			"R" | "FLOAT" => Self::Real,
-			"C" | "COMPLEX" => Self::Complex,
-			"MATRIX" => Self::Matrix,
-			_ => unimplemented!(),
+			"N" | "NATURAL" => Some(Self::Natural),
+			"Z" | "INT" | "INTEGER" => Some(Self::Int),
+			"Q" | "RATIONAL" => Some(Self::Rational),
+			"R" | "FLOAT" => Some(Self::Real),
+			"C" | "COMPLEX" => Some(Self::Complex),
+			"MATRIX" => Some(Self::Matrix),
+			"BOOL" | "BOOLEAN" => Some(Self::Bool),
+			_ => None,
 		}
 	}
+
+	/// `N` and `Q` refine `Z` and `R`, their values are stored as the type they refine
+	pub fn base(self) -> Self {
+		match self {
+			Self::Natural => Self::Int,
+			Self::Rational => Self::Real,
+			r#type => r#type,
+		}
+	}
+
+	/// Whether the type is a set of numbers that can appear on the right of `E`
+	pub fn is_number_set(self) -> bool {
+		!matches!(self, Self::Matrix | Self::Bool | Self::Unknown)
+	}
+
+	/// Names that can be written in a type annotation
+	pub fn dictionary() -> Vec<String> {
+		[
+			Self::Natural,
+			Self::Int,
+			Self::Rational,
+			Self::Real,
+			Self::Complex,
+			Self::Matrix,
+			Self::Bool,
+		]
+			.iter()
+			.map(|f| f.to_string())
+			.collect()
+	}
 }
 
 impl Display for NumberType {
@@ -27,10 +63,13 @@ impl Display for NumberType {
 			f,
 			"{}",
 			match self {
+				NumberType::Natural => "N",
 				NumberType::Int => "Z",
+				NumberType::Rational => "Q",
 				NumberType::Real => "R",
 				NumberType::Complex => "C",
 				NumberType::Matrix => "Matrix",
+				NumberType::Bool => "Bool",
 				NumberType::Unknown => "Unknown"
 			}
 		)